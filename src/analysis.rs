@@ -0,0 +1,183 @@
+// Отчёты по готовому дереву: расширения, крупнейшие файлы, дубликаты
+use crate::Node;
+use rayon::prelude::*;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::Path;
+
+// расширения во всём поддереве с суммарным размером, крупные сверху; сам узел "Прочее" не учитывается, его файлы учитываются
+// (расширение, размер, файлов)
+pub type ExtensionTotals = Vec<(String, u64, u64)>;
+
+pub fn extension_totals(node: &Node) -> ExtensionTotals {
+    fn collect(node: &Node, totals: &mut HashMap<String, (u64, u64)>) {
+        if !node.is_dir && !node.is_aggregated {
+            let ext = node.path.extension().map_or(String::new(), |e| e.to_string_lossy().to_lowercase());
+            let entry = totals.entry(ext).or_default();
+            entry.0 += node.size;
+            entry.1 += 1;
+        }
+        for child in &node.children {
+            collect(child, totals);
+        }
+    }
+    let mut by_ext = HashMap::new();
+    collect(node, &mut by_ext);
+    let mut totals: ExtensionTotals = by_ext.into_iter().map(|(ext, (size, count))| (ext, size, count)).collect();
+    totals.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    totals
+}
+
+pub fn largest_file(node: &Node) -> Option<&Node> {
+    if !node.is_dir {
+        // "Прочее" из импорта хранится без списка файлов, это не файл
+        return (!node.is_aggregated).then_some(node);
+    }
+    node.children.iter()
+        .filter_map(largest_file)
+        .max_by_key(|n| n.size)
+}
+
+// --find-dupes: хэшируем только файлы, у которых нашёлся ровесник по длине; группы — от самых затратных.
+// Сравнивается длина, а не size: при --disk-usage там занятые блоки
+pub fn find_duplicates(root: &Node) -> Vec<(u64, Vec<&Path>)> {
+    fn collect<'a>(node: &'a Node, by_size: &mut HashMap<u64, Vec<&'a Path>>) {
+        if node.is_dir {
+            for child in &node.children {
+                collect(child, by_size);
+            }
+        } else if !node.is_aggregated && node.apparent_size > 0 {
+            by_size.entry(node.apparent_size).or_default().push(&node.path);
+        }
+    }
+
+    let mut by_size = HashMap::new();
+    collect(root, &mut by_size);
+    let candidates: Vec<(u64, &Path)> = by_size.into_iter()
+        .filter(|(_, paths)| paths.len() > 1)
+        .map(|(size, paths)| (size, unique_files(paths)))
+        .filter(|(_, paths)| paths.len() > 1)
+        .flat_map(|(size, paths)| paths.into_iter().map(move |path| (size, path)))
+        .collect();
+
+    // нечитаемый файл просто выпадает из сравнения
+    let hashed: Vec<((u64, u64), &Path)> = candidates.into_par_iter()
+        .filter_map(|(size, path)| hash_file(path).ok().map(|hash| ((size, hash), path)))
+        .collect();
+    let mut groups: HashMap<(u64, u64), Vec<&Path>> = HashMap::new();
+    for (key, path) in hashed {
+        groups.entry(key).or_default().push(path);
+    }
+
+    let mut groups: Vec<(u64, Vec<&Path>)> = groups.into_iter()
+        .filter(|(_, paths)| paths.len() > 1)
+        .map(|((size, _), mut paths)| {
+            paths.sort();
+            (size, paths)
+        })
+        .collect();
+    groups.sort_by(|a, b| {
+        let wasted = |(size, paths): &(u64, Vec<&Path>)| size * (paths.len() as u64 - 1);
+        wasted(b).cmp(&wasted(a)).then_with(|| a.1.cmp(&b.1))
+    });
+    groups
+}
+
+// жёсткие ссылки на один inode — один файл: удаление одной из них ничего не освобождает, остаётся первая по пути
+fn unique_files(mut paths: Vec<&Path>) -> Vec<&Path> {
+    paths.sort();
+    let mut seen = HashSet::new();
+    paths.into_iter()
+        .filter(|path| file_id(path).is_none_or(|id| seen.insert(id)))
+        .collect()
+}
+
+#[cfg(unix)]
+fn file_id(path: &Path) -> Option<(u64, u64)> {
+    use std::os::unix::fs::MetadataExt;
+    fs::metadata(path).ok().map(|m| (m.dev(), m.ino()))
+}
+
+#[cfg(not(unix))]
+fn file_id(_path: &Path) -> Option<(u64, u64)> {
+    None
+}
+
+// 64-битного seahash хватает, чтобы случайное совпадение разных файлов одного размера было невероятным
+fn hash_file(path: &Path) -> std::io::Result<u64> {
+    use std::hash::Hasher;
+    use std::io::Read;
+
+    let mut file = fs::File::open(path)?;
+    let mut hasher = seahash::SeaHasher::new();
+    // блоки всегда полные (кроме последнего), чтобы хэш не зависел от того, как читает ОС
+    let mut buf = vec![0u8; 64 * 1024];
+    loop {
+        let mut filled = 0;
+        while filled < buf.len() {
+            match file.read(&mut buf[filled..])? {
+                0 => break,
+                n => filled += n,
+            }
+        }
+        hasher.write(&buf[..filled]);
+        if filled < buf.len() {
+            return Ok(hasher.finish());
+        }
+    }
+}
+
+// --top: куча держит не больше count файлов, на вершине самый маленький из них
+pub fn largest_files(root: &Node, count: usize) -> Vec<(u64, &Path)> {
+    use std::cmp::Reverse;
+    use std::collections::BinaryHeap;
+
+    fn collect<'a>(node: &'a Node, count: usize, heap: &mut BinaryHeap<Reverse<(u64, &'a Path)>>) {
+        if node.is_dir {
+            for child in &node.children {
+                collect(child, count, heap);
+            }
+        } else if !node.is_aggregated {
+            heap.push(Reverse((node.size, &node.path)));
+            if heap.len() > count {
+                heap.pop();
+            }
+        }
+    }
+
+    let mut heap = BinaryHeap::with_capacity(count + 1);
+    collect(root, count, &mut heap);
+    // для Reverse сортировка по возрастанию — это от большего к меньшему
+    heap.into_sorted_vec().into_iter().map(|Reverse(file)| file).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{scan, ScanContext, ScanOptions};
+
+    // a/data и b/link — один inode, c/copy — настоящая копия: дубликатов два, а не три
+    #[cfg(unix)]
+    #[test]
+    fn hardlinks_are_not_duplicates() {
+        let root = std::env::temp_dir().join(format!("volume-inspector-dupes-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        for dir in ["a", "b", "c"] {
+            fs::create_dir_all(root.join(dir)).unwrap();
+        }
+        fs::write(root.join("a").join("data"), vec![7u8; 5000]).unwrap();
+        fs::hard_link(root.join("a").join("data"), root.join("b").join("link")).unwrap();
+        fs::write(root.join("c").join("copy"), vec![7u8; 5000]).unwrap();
+
+        let opts = ScanOptions { no_other: true, disk_usage: true, scan_root: root.clone(), ..Default::default() };
+        let tree = scan(&root, &opts, &ScanContext::default()).unwrap();
+        let groups = find_duplicates(&tree);
+        let ids: Vec<Option<(u64, u64)>> = groups.iter().flat_map(|(_, paths)| paths.iter().map(|p| file_id(p))).collect();
+        fs::remove_dir_all(&root).unwrap();
+
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0].0, 5000);
+        assert_eq!(groups[0].1.len(), 2);
+        assert_ne!(ids[0], ids[1]);
+    }
+}
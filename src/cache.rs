@@ -0,0 +1,56 @@
+// Кэш дерева: сохранённое сканирование открывается без повторного обхода диска
+use crate::{tr, Node};
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// меняется вместе с форматом Node: кэш другой версии отвергается, а не читается вслепую
+const CACHE_MAGIC: [u8; 8] = *b"vicache2";
+
+// заголовок лежит перед деревом и читается отдельно: для оценки ETA хватает числа файлов
+#[derive(Serialize, Deserialize)]
+pub struct CacheHeader {
+    pub root: PathBuf,
+    pub created: u64,
+    pub file_count: u64,
+}
+
+pub struct CacheFile {
+    pub header: CacheHeader,
+    pub tree: Node,
+}
+
+pub fn save_cache(file: &Path, root: &Path, tree: &Node) -> Result<()> {
+    let created = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs());
+    let mut writer = BufWriter::new(fs::File::create(file)?);
+    let header = CacheHeader { root: root.to_path_buf(), created, file_count: tree.file_count };
+    writer.write_all(&CACHE_MAGIC)?;
+    bincode::serialize_into(&mut writer, &header)?;
+    bincode::serialize_into(&mut writer, tree)?;
+    writer.flush()?;
+    Ok(())
+}
+
+// метка проверяется до разбора: чужие байты bincode принял бы за длину строки в эксабайты
+fn read_cache_header(reader: &mut impl Read) -> Result<CacheHeader> {
+    let mut magic = [0u8; 8];
+    reader.read_exact(&mut magic)?;
+    if magic != CACHE_MAGIC {
+        anyhow::bail!("{}", tr("кэш записан другой версией программы", "cache was written by a different version"));
+    }
+    Ok(bincode::deserialize_from(reader)?)
+}
+
+pub fn load_cache_header(file: &Path) -> Result<CacheHeader> {
+    read_cache_header(&mut BufReader::new(fs::File::open(file)?))
+}
+
+pub fn load_cache(file: &Path) -> Result<CacheFile> {
+    let mut reader = BufReader::new(fs::File::open(file)?);
+    let header = read_cache_header(&mut reader)?;
+    let tree = bincode::deserialize_from(reader)?;
+    Ok(CacheFile { header, tree })
+}
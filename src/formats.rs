@@ -0,0 +1,186 @@
+// Обмен с другими программами: экспорт и импорт в формате ncdu, импорт вывода du, CSV по расширениям
+use crate::analysis::extension_totals;
+use crate::{assemble_dir, tr, Node, ScanOptions};
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+use std::fs;
+use std::io::{BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// формат экспорта ncdu: [1, 2, {метаданные}, [{директория}, дети...]]
+pub fn export_ncdu(file: &Path, tree: &Node) -> Result<()> {
+    let mut out = BufWriter::new(fs::File::create(file)?);
+    let created = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs());
+    let meta = serde_json::json!({
+        "progname": env!("CARGO_PKG_NAME"),
+        "progver": env!("CARGO_PKG_VERSION"),
+        "timestamp": created,
+    });
+    write!(out, "[1,2,{},", meta)?;
+    write_ncdu_node(&mut out, tree, true)?;
+    writeln!(out, "]")?;
+    out.flush()?;
+    Ok(())
+}
+
+fn write_ncdu_node(out: &mut impl Write, node: &Node, is_root: bool) -> Result<()> {
+    let mut info = serde_json::Map::new();
+    let name = if is_root { node.path.display().to_string() } else { node.name.clone() };
+    info.insert("name".into(), name.into());
+    // "Прочее" без списка файлов (из импорта) уходит одним файлом
+    let is_file = !node.is_dir || node.is_aggregated;
+    if is_file {
+        info.insert("asize".into(), node.apparent_size.into());
+        info.insert("dsize".into(), node.disk_size.into());
+    }
+    if let Some(mtime) = node.mtime.and_then(|t| t.duration_since(UNIX_EPOCH).ok()) {
+        info.insert("mtime".into(), mtime.as_secs().into());
+    }
+    if node.is_aggregated {
+        // у ncdu нет сводных узлов: для ncdu это нестандартный файл, незнакомый ключ он пропустит
+        info.insert("notreg".into(), true.into());
+        info.insert("aggregated".into(), true.into());
+    }
+    if node.incomplete {
+        info.insert("read_error".into(), true.into());
+    }
+
+    if is_file {
+        write!(out, "{}", serde_json::Value::Object(info))?;
+        return Ok(());
+    }
+    write!(out, "[{}", serde_json::Value::Object(info))?;
+    // файлы "Прочее" на самом деле лежат в этой директории, туда их и пишем
+    let entries = node.children.iter().flat_map(|child| {
+        if child.is_aggregated && !child.children.is_empty() {
+            &child.children[..]
+        } else {
+            std::slice::from_ref(child)
+        }
+    });
+    for child in entries {
+        write!(out, ",")?;
+        write_ncdu_node(out, child, false)?;
+    }
+    write!(out, "]")?;
+    Ok(())
+}
+
+// файлы "Прочее" считаются по своим расширениям; сводный узел без списка файлов (из импорта) не попадает никуда
+pub fn export_csv(file: &Path, tree: &Node) -> Result<()> {
+    let mut out = BufWriter::new(fs::File::create(file)?);
+    writeln!(out, "extension,total_bytes,file_count")?;
+    for (ext, size, count) in extension_totals(tree) {
+        writeln!(out, "{},{},{}", csv_field(&ext), size, count)?;
+    }
+    out.flush()?;
+    Ok(())
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+pub fn import_ncdu(file: &Path, opts: &ScanOptions) -> Result<Node> {
+    let reader = BufReader::new(fs::File::open(file)?);
+    let export: serde_json::Value = serde_json::from_reader(reader)?;
+    let Some(root) = export.get(3) else {
+        anyhow::bail!("{}: {}", file.display(), tr("это не экспорт ncdu", "not an ncdu export"));
+    };
+    ncdu_to_node(root, Path::new(""), opts, 0)
+}
+
+// директория в ncdu — массив [информация, дети...], файл — просто объект
+fn ncdu_to_node(value: &serde_json::Value, parent: &Path, opts: &ScanOptions, depth: usize) -> Result<Node> {
+    let (info, children) = match value {
+        serde_json::Value::Array(items) if !items.is_empty() => (&items[0], Some(&items[1..])),
+        serde_json::Value::Object(_) => (value, None),
+        _ => anyhow::bail!("{}", tr("неожиданная запись в экспорте ncdu", "unexpected entry in ncdu export")),
+    };
+    let name = info["name"].as_str().unwrap_or_default();
+    let path = parent.join(name);
+    let mtime = info["mtime"].as_u64().map(|secs| UNIX_EPOCH + std::time::Duration::from_secs(secs));
+
+    let Some(children) = children else {
+        let (asize, dsize) = (info["asize"].as_u64(), info["dsize"].as_u64());
+        let (apparent_size, disk_size) = (asize.or(dsize).unwrap_or(0), dsize.or(asize).unwrap_or(0));
+        return Ok(Node {
+            name: name.to_string(),
+            size: if opts.disk_usage { disk_size } else { apparent_size },
+            apparent_size,
+            disk_size,
+            path,
+            mtime,
+            newest_mtime: mtime,
+            oldest_mtime: mtime,
+            file_count: 1,
+            is_aggregated: info["aggregated"].as_bool().unwrap_or(false),
+            ..Default::default()
+        });
+    };
+
+    let children = children.iter()
+        .map(|child| ncdu_to_node(child, &path, opts, depth + 1))
+        .collect::<Result<Vec<_>>>()?;
+    let read_error = info["read_error"].as_bool().unwrap_or(false);
+    let mut node = assemble_dir(&path, children, read_error, opts, depth);
+    node.mtime = mtime;
+    Ok(node)
+}
+
+pub fn import_du(file: &Path, opts: &ScanOptions) -> Result<Node> {
+    let text = fs::read_to_string(file)?;
+    let mut sizes: HashMap<PathBuf, u64> = HashMap::new();
+    for (index, line) in text.lines().enumerate().filter(|(_, l)| !l.trim().is_empty()) {
+        let lineno = index + 1;
+        let Some((size, path)) = line.split_once('\t') else {
+            anyhow::bail!("{}:{}: {}: {}", file.display(), lineno, tr("ожидалось 'размер<TAB>путь'", "expected 'size<TAB>path'"), line);
+        };
+        let size: u64 = size.trim().parse().with_context(|| format!("{}:{}", file.display(), lineno))?;
+        sizes.insert(PathBuf::from(path), size);
+    }
+
+    // корень — самый короткий путь, du печатает его последним
+    let Some(root) = sizes.keys().min_by_key(|p| p.components().count()).cloned() else {
+        anyhow::bail!("{}: {}", file.display(), tr("пустой вывод du", "empty du output"));
+    };
+    let mut children: HashMap<PathBuf, Vec<PathBuf>> = HashMap::new();
+    for path in sizes.keys().filter(|p| **p != root) {
+        if let Some(parent) = path.parent() {
+            children.entry(parent.to_path_buf()).or_default().push(path.clone());
+        }
+    }
+    Ok(du_to_node(&root, &sizes, &mut children, opts, 0))
+}
+
+// du не отличает пустые директории от файлов, поэтому директорией считаем всё, у чего есть дети
+fn du_to_node(
+    path: &Path,
+    sizes: &HashMap<PathBuf, u64>,
+    children: &mut HashMap<PathBuf, Vec<PathBuf>>,
+    opts: &ScanOptions,
+    depth: usize,
+) -> Node {
+    let Some(child_paths) = children.remove(path) else {
+        // du печатает только одно измерение, его и считаем обоими
+        let size = sizes.get(path).copied().unwrap_or(0);
+        return Node {
+            name: path.file_name().map_or("".to_string(), |s| s.to_string_lossy().into_owned()),
+            size,
+            apparent_size: size,
+            disk_size: size,
+            path: path.to_path_buf(),
+            file_count: 1,
+            ..Default::default()
+        };
+    };
+    let nodes = child_paths.iter()
+        .map(|child| du_to_node(child, sizes, children, opts, depth + 1))
+        .collect();
+    assemble_dir(path, nodes, false, opts, depth)
+}
@@ -0,0 +1,965 @@
+// Сканер: обходит директорию и строит дерево размеров Node. Бинарник volume-inspector рисует его в терминале
+use anyhow::Result;
+use std::sync::atomic::{AtomicU64, AtomicBool, Ordering};
+use std::borrow::Cow;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::collections::{HashMap, HashSet};
+use std::sync::Mutex;
+use rayon::prelude::*;
+use ignore::gitignore::Gitignore;
+use ignore::Match;
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use regex::Regex;
+use std::sync::Arc;
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant, SystemTime};
+
+pub mod analysis;
+pub mod cache;
+pub mod formats;
+pub mod treemap;
+#[cfg(test)]
+mod test_support;
+
+// язык сообщений, выставляется один раз из --lang; ошибки импорта и кэша тоже переводятся
+pub static ENGLISH: AtomicBool = AtomicBool::new(false);
+
+pub fn tr(ru: &'static str, en: &'static str) -> &'static str {
+    if ENGLISH.load(Ordering::Relaxed) { en } else { ru }
+}
+
+/// Состояние одного вызова [`scan`]: счётчики, ошибки чтения и флаг отмены.
+/// Создаётся вызывающим и живёт у него, поэтому счётчики можно читать из другого потока, пока идёт сканирование.
+#[derive(Default)]
+pub struct ScanContext {
+    // выставляется снаружи, например из обработчика Ctrl+C; дерево возвращается неполным
+    pub cancel: AtomicBool,
+    pub scanned_files: AtomicU64,
+    // --respect-gitignore: сколько записей пропущено в каждой директории, чтобы пересканирование поддерева заменяло только его счёт
+    pub ignored_entries: Mutex<HashMap<PathBuf, u64>>,
+    // пути, которые не удалось прочитать; сканирование при этом продолжается
+    pub errors: Mutex<Vec<(PathBuf, std::io::Error)>>,
+    // --profile: счётчики системных вызовов и время чтения каждой директории
+    pub read_dir_calls: AtomicU64,
+    pub metadata_calls: AtomicU64,
+    pub dir_timings: Mutex<Vec<(PathBuf, Duration)>>,
+    // канонические пути уже обойдённых директорий, нужны только при --follow-symlinks
+    visited_dirs: Mutex<HashSet<PathBuf>>,
+    // (device, inode) уже посчитанных файлов с несколькими жёсткими ссылками → копия, на которую записан размер
+    hardlinks: Mutex<HardlinkOwners>,
+}
+
+pub type HardlinkOwners = HashMap<(u64, u64), PathBuf>;
+
+impl ScanContext {
+    /// Контекст для пересканирования поддерева: жёсткие ссылки, уже посчитанные в остальном дереве,
+    /// второй раз не считаются. Владельцы внутри `subtree` забываются, их заново выберет это сканирование.
+    pub fn for_subtree(mut hardlinks: HardlinkOwners, subtree: &Path) -> Self {
+        hardlinks.retain(|_, path| !path.starts_with(subtree));
+        ScanContext {
+            hardlinks: Mutex::new(hardlinks),
+            ..Default::default()
+        }
+    }
+
+    // забирает владельцев жёстких ссылок после сканирования, чтобы передать их следующему for_subtree
+    pub fn take_hardlinks(&self) -> HardlinkOwners {
+        std::mem::take(&mut self.hardlinks.lock().unwrap())
+    }
+}
+
+pub type ProgressFn = Arc<dyn Fn(&Path, usize) + Send + Sync>;
+
+#[derive(Clone, Default)]
+pub struct ScanOptions {
+    pub ignore_permission_errors: bool,
+    pub max_depth: Option<usize>,
+    pub follow_symlinks: bool,
+    pub count_hardlinks_once: bool,
+    pub disk_usage: bool,
+    // устройство корня сканирования, если включён --one-file-system
+    pub root_dev: Option<u64>,
+    pub min_size: Option<u64>,
+    // --no-other: каждый файл своей плиткой, без "Прочее"
+    pub no_other: bool,
+    // --other-factors вместо OTHER_TIER_FACTORS
+    pub other_factors: Option<[f64; 3]>,
+    // директории перед файлами при любой сортировке
+    pub dirs_first: bool,
+    pub respect_gitignore: bool,
+    pub scan_root: PathBuf,
+    pub exclude: Option<GlobSet>,
+    pub exclude_hidden: bool,
+    // --name-filter: в дерево попадают только файлы с подходящим именем, опустевшие директории выпадают
+    pub name_filter: Option<Regex>,
+    // --exclude-empty: без пустых файлов и директорий нулевого размера
+    pub exclude_empty: bool,
+    // имя узла "Прочее" в интерфейсе, по умолчанию "Other"
+    pub other_name: Option<String>,
+    // --profile: собирать время чтения директорий в ScanContext::dir_timings
+    pub profile: bool,
+    // вызывается на входе в каждую директорию с её глубиной от scan_root, из потоков rayon — должен быть быстрым
+    pub progress: Option<ProgressFn>,
+}
+
+pub fn build_globset(patterns: &[String]) -> Result<Option<GlobSet>> {
+    if patterns.is_empty() {
+        return Ok(None);
+    }
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        builder.add(Glob::new(pattern)?);
+    }
+    Ok(Some(builder.build()?))
+}
+
+fn is_excluded(opts: &ScanOptions, path: &Path) -> bool {
+    let Some(exclude) = &opts.exclude else {
+        return false;
+    };
+    path.strip_prefix(&opts.scan_root).is_ok_and(|rel| exclude.is_match(rel))
+}
+
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct Node {
+    pub name: String,
+    pub size: u64,
+    // оба измерения: size равен одному из них, 'u' переключает без пересканирования
+    pub apparent_size: u64,
+    pub disk_size: u64,
+    pub path: PathBuf,
+    pub children: Vec<Node>,
+    pub is_dir: bool,
+    pub mtime: Option<SystemTime>,
+    // самый свежий и самый старый mtime файлов поддерева; у файла оба равны mtime
+    pub newest_mtime: Option<SystemTime>,
+    pub oldest_mtime: Option<SystemTime>,
+    pub incomplete: bool,
+    pub file_count: u64,
+    pub dir_count: u64,
+    // сводный узел "Прочее" для мелких файлов
+    pub is_aggregated: bool,
+    // у "Прочее": порог, ниже которого файлы попали в сводный узел
+    pub threshold: u64,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum SortMode {
+    Size,
+    Name,
+    Count,
+    Mtime,
+}
+
+impl SortMode {
+    pub fn next(self) -> Self {
+        match self {
+            SortMode::Size => SortMode::Name,
+            SortMode::Name => SortMode::Count,
+            SortMode::Count => SortMode::Mtime,
+            SortMode::Mtime => SortMode::Size,
+        }
+    }
+}
+
+pub fn sort_nodes(nodes: &mut [Node], mode: SortMode, dirs_first: bool) {
+    nodes.sort_by(|a, b| {
+        let order = match mode {
+            SortMode::Size => b.total_size().cmp(&a.total_size()),
+            SortMode::Name => a.name.to_lowercase().cmp(&b.name.to_lowercase()),
+            SortMode::Count => b.children.len().cmp(&a.children.len()),
+            SortMode::Mtime => b.mtime.cmp(&a.mtime),
+        };
+        // "Прочее" всегда в конце, при равенстве сортируем по имени, чтобы порядок был стабильным
+        let dirs = if dirs_first { b.is_dir.cmp(&a.is_dir) } else { std::cmp::Ordering::Equal };
+        a.is_aggregated.cmp(&b.is_aggregated)
+            .then(dirs)
+            .then(order)
+            .then_with(|| a.name.cmp(&b.name))
+    });
+}
+
+pub fn sort_tree(node: &mut Node, mode: SortMode, dirs_first: bool) {
+    sort_nodes(&mut node.children, mode, dirs_first);
+    for child in &mut node.children {
+        sort_tree(child, mode, dirs_first);
+    }
+}
+
+impl Node {
+    pub fn total_size(&self) -> u64 {
+        self.size
+    }
+
+    // сколько файлов и папок узел добавляет к счётчикам родителя; "Прочее" — не папка на диске
+    pub fn entry_counts(&self) -> (u64, u64) {
+        (self.file_count, self.dir_count + (self.is_dir && !self.is_aggregated) as u64)
+    }
+
+    // копия узла без поддерева, с другими детьми: для представлений, которые перестраивают дерево
+    pub fn with_children(&self, children: Vec<Node>) -> Node {
+        Node {
+            name: self.name.clone(),
+            path: self.path.clone(),
+            children,
+            ..*self
+        }
+    }
+
+    // size по всему поддереву берётся из disk_size или apparent_size
+    pub fn use_disk_size(&mut self, disk: bool) {
+        self.size = if disk { self.disk_size } else { self.apparent_size };
+        for child in &mut self.children {
+            child.use_disk_size(disk);
+        }
+    }
+}
+
+// (newest, oldest) по детям; пустые директории в диапазон не попадают
+pub fn mtime_range(children: &[Node]) -> (Option<SystemTime>, Option<SystemTime>) {
+    let newest = children.iter().filter_map(|c| c.newest_mtime).max();
+    let oldest = children.iter().filter_map(|c| c.oldest_mtime).min();
+    (newest, oldest)
+}
+
+// Windows: путь длиннее 260 символов открывается только в расширенной записи \\?\C:\... или \\?\UNC\сервер\...
+// Дети получают пути через join, поэтому префикс корня достаётся всему дереву
+#[cfg(windows)]
+pub fn long_path(path: &Path) -> Cow<'_, Path> {
+    use std::path::{Component, Prefix};
+    let (Some(Component::Prefix(prefix)), Some(text)) = (path.components().next(), path.to_str()) else {
+        return Cow::Borrowed(path);
+    };
+    match prefix.kind() {
+        Prefix::Disk(_) if path.is_absolute() => Cow::Owned(PathBuf::from(format!(r"\\?\{}", text))),
+        Prefix::UNC(..) => Cow::Owned(PathBuf::from(format!(r"\\?\UNC\{}", &text[2..]))),
+        _ => Cow::Borrowed(path),
+    }
+}
+
+#[cfg(not(windows))]
+pub fn long_path(path: &Path) -> Cow<'_, Path> {
+    Cow::Borrowed(path)
+}
+
+fn build_tree(root: &Path, opts: &ScanOptions, ctx: &ScanContext, depth: usize, ignores: &[Arc<Gitignore>]) -> Result<Node> {
+    let root = long_path(root);
+    let root = root.as_ref();
+    if let Some(progress) = &opts.progress {
+        progress(root, depth);
+    }
+
+    if ctx.cancel.load(Ordering::Relaxed) {
+        return Ok(Node {
+            name: root.file_name().map_or("".to_string(), |s| s.to_string_lossy().into_owned()),
+            path: root.to_path_buf(),
+            is_dir: true,
+            incomplete: true,
+            ..Default::default()
+        });
+    }
+
+    if opts.follow_symlinks
+        && let Ok(canonical) = fs::canonicalize(root)
+    {
+        ctx.visited_dirs.lock().unwrap().insert(canonical);
+    }
+
+    let listing_start = Instant::now();
+    ctx.read_dir_calls.fetch_add(1, Ordering::Relaxed);
+    let read_dir = match fs::read_dir(root) {
+        Ok(rd) => rd,
+        Err(e) => {
+            record_scan_error(opts, ctx, root, e);
+            // содержимое неизвестно: рисуется с пунктирной рамкой, в экспорт ncdu попадает read_error
+            return Ok(Node {
+                name: root.file_name().map_or("".to_string(), |s| s.to_string_lossy().into_owned()),
+                size: 0,
+                path: root.to_path_buf(),
+                children: Vec::new(),
+                is_dir: true,
+                incomplete: true,
+                ..Default::default()
+            });
+        }
+    };
+
+    let mut local_ignores;
+    let mut ignores = ignores;
+    if opts.respect_gitignore
+        && let Some(gitignore) = load_gitignore(root)
+    {
+        local_ignores = ignores.to_vec();
+        local_ignores.push(gitignore);
+        ignores = &local_ignores;
+    }
+
+    let mut entries = Vec::new();
+    let mut ignored = 0u64;
+    for entry in read_dir {
+        match entry {
+            Ok(e) if opts.respect_gitignore && is_gitignored(ignores, &e.path(), e.file_type().is_ok_and(|t| t.is_dir())) => ignored += 1,
+            Ok(e) if is_excluded(opts, &e.path()) => {}
+            Ok(e) if opts.exclude_hidden && is_hidden(&e) => {}
+            Ok(e) => entries.push(e),
+            Err(e) => record_scan_error(opts, ctx, root, e),
+        }
+    }
+    if ignored > 0 {
+        ctx.ignored_entries.lock().unwrap().insert(root.to_path_buf(), ignored);
+    }
+    // собственное время директории: только чтение списка, без поддиректорий и метаданных записей
+    if opts.profile {
+        ctx.dir_timings.lock().unwrap().push((root.to_path_buf(), listing_start.elapsed()));
+    }
+
+    // поддиректории обходятся параллельно, порядок результатов совпадает с порядком entries
+    let skipped = AtomicBool::new(false);
+    let children: Vec<Node> = entries
+        .into_par_iter()
+        .map(|entry| {
+            if ctx.cancel.load(Ordering::Relaxed) {
+                skipped.store(true, Ordering::Relaxed);
+                return Ok(None);
+            }
+            build_entry(&entry, opts, ctx, depth, ignores)
+        })
+        .collect::<Result<Vec<_>>>()?
+        .into_iter()
+        .flatten()
+        .collect();
+
+    Ok(assemble_dir(root, children, skipped.into_inner(), opts, depth))
+}
+
+/// Сканирует `path` (директорию внутри `opts.scan_root` или сам корень) и возвращает дерево размеров.
+/// `opts.progress`, если задан, получает каждую директорию при входе в неё и её глубину от `opts.scan_root`.
+/// Счётчики, ошибки и отмена этого вызова живут в `ctx`; для каждого сканирования обычно берётся новый контекст.
+pub fn scan(path: &Path, opts: &ScanOptions, ctx: &ScanContext) -> Result<Node> {
+    let depth = path.strip_prefix(&opts.scan_root).map_or(0, |rel| rel.components().count());
+
+    // правила .gitignore из директорий между корнем и path
+    let ignores = if opts.respect_gitignore {
+        parent_gitignores(&opts.scan_root, path)
+    } else {
+        Vec::new()
+    };
+    build_tree(path, opts, ctx, depth, &ignores)
+}
+
+/// Узел одной записи `path` внутри `opts.scan_root` с теми же фильтрами, что и при обходе её директории;
+/// директория сканируется целиком. `None`, если записи больше нет или она отфильтрована.
+pub fn scan_entry(path: &Path, opts: &ScanOptions, ctx: &ScanContext) -> Result<Option<Node>> {
+    ctx.metadata_calls.fetch_add(1, Ordering::Relaxed);
+    let metadata = match fs::symlink_metadata(path) {
+        Ok(m) => m,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => {
+            record_scan_error(opts, ctx, path, e);
+            return Ok(None);
+        }
+    };
+    if is_excluded(opts, path) || opts.exclude_hidden && is_hidden_path(path, &metadata) {
+        return Ok(None);
+    }
+    let ignores = if opts.respect_gitignore {
+        parent_gitignores(&opts.scan_root, path)
+    } else {
+        Vec::new()
+    };
+    if opts.respect_gitignore && is_gitignored(&ignores, path, metadata.is_dir()) {
+        return Ok(None);
+    }
+
+    // глубина директории, в которой лежит запись
+    let depth = path.parent()
+        .and_then(|dir| dir.strip_prefix(&opts.scan_root).ok())
+        .map_or(0, |rel| rel.components().count());
+    if metadata.is_symlink() {
+        if !opts.follow_symlinks {
+            return Ok(None);
+        }
+        return build_symlink(path, opts, ctx, depth, &ignores);
+    }
+    build_resolved(path, &metadata, opts, ctx, depth, &ignores)
+}
+
+// последний компонент пути "Прочее": узел лежит как бы внутри своей директории и не совпадает с ней по пути.
+// NUL запрещён в именах файлов везде, поэтому настоящий файл с таким путём не совпадёт
+pub const OTHER_MARKER: &str = "\0other";
+// так путь "Прочее" выглядит в интерфейсе
+pub const OTHER_SHOWN: &str = "<other>";
+
+pub fn is_other_path(path: &Path) -> bool {
+    path.file_name().is_some_and(|name| name == OTHER_MARKER)
+}
+
+// путь для сводной плитки представления (группа по расширению и т.п.): как у "Прочее", имя начинается с NUL
+pub fn synthetic_path(dir: &Path, key: &str) -> PathBuf {
+    dir.join(format!("\0{}", key))
+}
+
+// "Прочее" или плитка представления: на диске такого пути нет
+pub fn is_synthetic_path(path: &Path) -> bool {
+    path.file_name().is_some_and(|name| name.as_encoded_bytes().first() == Some(&0))
+}
+
+// директория на диске, к которой относится путь из дерева: для "Прочее" и плиток представлений это их родитель
+pub fn real_dir(path: &Path) -> &Path {
+    if is_synthetic_path(path) {
+        path.parent().unwrap_or(path)
+    } else {
+        path
+    }
+}
+
+// может ли путь оказаться в поддереве узла; файлы "Прочее" лежат в его родителе, а не под его путём
+pub fn may_contain(node: &Node, path: &Path) -> bool {
+    path.starts_with(&node.path) || node.is_aggregated && is_other_path(&node.path) && path.parent() == node.path.parent()
+}
+
+// доли размера директории для порога "Прочее": до 50 файлов, до 200, больше
+const OTHER_TIER_FACTORS: [f64; 3] = [0.01, 0.005, 0.001];
+
+// чем больше файлов, тем меньшая доля директории уходит в "Прочее", но не меньше 1 КБ и пятой части среднего файла
+fn compute_other_threshold(file_count: u64, total_size: u64, file_total_size: u64, factors: [f64; 3]) -> u64 {
+    if file_count == 0 {
+        return u64::MAX;
+    }
+    let avg_size = file_total_size as f64 / file_count as f64;
+    let count_factor = if file_count > 200 {
+        factors[2]
+    } else if file_count > 50 {
+        factors[1]
+    } else {
+        factors[0]
+    };
+    let size_based = total_size as f64 * count_factor;
+    let avg_based = avg_size * 0.2;
+    size_based.max(avg_based).max(1024.0) as u64
+}
+
+// общая часть для сканирования и импорта: итоги, сортировка, "Прочее", лимит глубины
+pub fn assemble_dir(path: &Path, mut children: Vec<Node>, skipped: bool, opts: &ScanOptions, depth: usize) -> Node {
+    let mut total_size = 0u64;
+    let mut apparent_size = 0u64;
+    let mut disk_size = 0u64;
+    let mut file_count = 0;
+    let mut file_total_size = 0u64;
+    let incomplete = skipped || children.iter().any(|c| c.incomplete);
+    let (newest_mtime, oldest_mtime) = mtime_range(&children);
+    let mut total_files = 0u64;
+    let mut total_dirs = 0u64;
+    for child in &children {
+        let (files, dirs) = child.entry_counts();
+        total_files += files;
+        total_dirs += dirs;
+        total_size += child.total_size();
+        apparent_size += child.apparent_size;
+        disk_size += child.disk_size;
+        if !child.is_dir {
+            file_total_size += child.size;
+            file_count += 1;
+        }
+    }
+
+    // порядок не должен зависеть от планировщика
+    sort_nodes(&mut children, SortMode::Size, opts.dirs_first);
+
+    let threshold = if opts.no_other {
+        0
+    } else if let Some(min_size) = opts.min_size {
+        min_size
+    } else {
+        let factors = opts.other_factors.unwrap_or(OTHER_TIER_FACTORS);
+        compute_other_threshold(file_count, total_size, file_total_size, factors)
+    };
+
+    let mut other_size = 0u64;
+    let mut other_apparent = 0u64;
+    let mut other_disk = 0u64;
+    let mut other_count = 0u64;
+    let mut other_children = Vec::new();
+    let mut filtered = Vec::new();
+
+    for child in children {
+        if child.is_aggregated {
+            // уже сводный узел (из импорта) сливаем с новым, чтобы не было двух "Прочее"
+            other_size += child.size;
+            other_apparent += child.apparent_size;
+            other_disk += child.disk_size;
+            other_count += child.file_count;
+            other_children.extend(child.children);
+        } else if !child.is_dir && child.size < threshold {
+            other_size += child.size;
+            other_apparent += child.apparent_size;
+            other_disk += child.disk_size;
+            other_count += 1;
+            other_children.push(child);
+        } else {
+            filtered.push(child);
+        }
+    }
+
+    if other_size > 0 {
+        let (other_newest, other_oldest) = mtime_range(&other_children);
+        filtered.push(Node {
+            name: opts.other_name.as_deref().unwrap_or("Other").to_string(),
+            size: other_size,
+            apparent_size: other_apparent,
+            disk_size: other_disk,
+            path: path.join(OTHER_MARKER),
+            children: other_children,
+            is_dir: true,
+            file_count: other_count,
+            newest_mtime: other_newest,
+            oldest_mtime: other_oldest,
+            is_aggregated: true,
+            threshold,
+            ..Default::default()
+        });
+    }
+
+    // глубже лимита директория становится листом, но её размер уже посчитан полностью
+    if opts.max_depth.is_some_and(|max| depth > max) {
+        filtered.clear();
+    }
+
+    let name = path.file_name().map_or("".to_string(), |s| s.to_string_lossy().into_owned());
+
+    Node {
+        name,
+        size: total_size,
+        apparent_size,
+        disk_size,
+        path: path.to_path_buf(),
+        children: filtered,
+        is_dir: true,
+        newest_mtime,
+        oldest_mtime,
+        incomplete,
+        file_count: total_files,
+        dir_count: total_dirs,
+        ..Default::default()
+    }
+}
+
+// заново раскладывает файлы по "Прочее" во всём дереве, когда size сменил измерение ('u'): порог и состав
+// считаются по новому размеру. Директории без детей (лимит глубины, точки монтирования) остаются как есть
+pub fn regroup_other(node: &mut Node, opts: &ScanOptions, depth: usize) {
+    if !node.is_dir || node.is_aggregated || node.children.is_empty() {
+        return;
+    }
+    let mut children: Vec<Node> = std::mem::take(&mut node.children).into_iter()
+        .flat_map(|c| if c.is_aggregated && !c.children.is_empty() { c.children } else { vec![c] })
+        .collect();
+    for child in &mut children {
+        regroup_other(child, opts, depth + 1);
+    }
+    let skipped = node.incomplete && !children.iter().any(|c| c.incomplete);
+    let mut rebuilt = assemble_dir(&node.path, children, skipped, opts, depth);
+    // имя корня может быть подписью --root-label
+    rebuilt.name = std::mem::take(&mut node.name);
+    rebuilt.mtime = node.mtime;
+    *node = rebuilt;
+}
+
+fn load_gitignore(dir: &Path) -> Option<Arc<Gitignore>> {
+    let path = dir.join(".gitignore");
+    if !path.is_file() {
+        return None;
+    }
+    let (gitignore, _) = Gitignore::new(path);
+    Some(Arc::new(gitignore))
+}
+
+// .gitignore всех директорий от корня сканирования до dir (не включая её саму)
+fn parent_gitignores(scan_root: &Path, dir: &Path) -> Vec<Arc<Gitignore>> {
+    let mut ignores = Vec::new();
+    for ancestor in dir.ancestors().skip(1) {
+        if !ancestor.starts_with(scan_root) {
+            break;
+        }
+        ignores.extend(load_gitignore(ancestor));
+    }
+    ignores.reverse();
+    ignores
+}
+
+// ближайший .gitignore имеет приоритет, как в git
+fn is_gitignored(ignores: &[Arc<Gitignore>], path: &Path, is_dir: bool) -> bool {
+    for gitignore in ignores.iter().rev() {
+        match gitignore.matched(path, is_dir) {
+            Match::Ignore(_) => return true,
+            Match::Whitelist(_) => return false,
+            Match::None => {}
+        }
+    }
+    false
+}
+
+// --ignore-permission-errors прячет отказы в доступе и гонки с удалением, остальное попадает в список ошибок
+fn record_scan_error(opts: &ScanOptions, ctx: &ScanContext, path: &Path, error: std::io::Error) {
+    if opts.ignore_permission_errors
+        && matches!(error.kind(), std::io::ErrorKind::PermissionDenied | std::io::ErrorKind::NotFound)
+    {
+        return;
+    }
+    ctx.errors.lock().unwrap().push((path.to_path_buf(), error));
+}
+
+fn build_entry(entry: &fs::DirEntry, opts: &ScanOptions, ctx: &ScanContext, depth: usize, ignores: &[Arc<Gitignore>]) -> Result<Option<Node>> {
+    let path = entry.path();
+
+    ctx.metadata_calls.fetch_add(1, Ordering::Relaxed);
+    let metadata = match entry.metadata() {
+        Ok(m) => m,
+        Err(e) => {
+            record_scan_error(opts, ctx, &path, e);
+            return Ok(None);
+        }
+    };
+
+    if metadata.is_symlink() {
+        if !opts.follow_symlinks {
+            return Ok(None);
+        }
+        return build_symlink(&path, opts, ctx, depth, ignores);
+    }
+    build_resolved(&path, &metadata, opts, ctx, depth, ignores)
+}
+
+// узел записи по её метаданным (у симлинка — по метаданным цели); None, если запись отфильтрована
+fn build_resolved(
+    path: &Path,
+    metadata: &fs::Metadata,
+    opts: &ScanOptions,
+    ctx: &ScanContext,
+    depth: usize,
+    ignores: &[Arc<Gitignore>],
+) -> Result<Option<Node>> {
+    if metadata.is_dir() {
+        if is_other_filesystem(metadata, opts) {
+            return Ok(Some(mount_point_node(path, metadata)));
+        }
+        let mut node = build_tree(path, opts, ctx, depth + 1, ignores)?;
+        if opts.name_filter.is_some() && node.file_count == 0 && !node.incomplete {
+            return Ok(None);
+        }
+        if opts.exclude_empty && node.size == 0 && !node.incomplete {
+            return Ok(None);
+        }
+        node.mtime = metadata.modified().ok();
+        return Ok(Some(node));
+    }
+
+    if !metadata.is_file() {
+        return Ok(None);
+    }
+
+    ctx.scanned_files.fetch_add(1, Ordering::Relaxed);
+
+    let name = path.file_name().map_or("".to_string(), |s| s.to_string_lossy().into_owned());
+    if opts.name_filter.as_ref().is_some_and(|filter| !filter.is_match(&name)) {
+        return Ok(None);
+    }
+    if opts.exclude_empty && metadata.len() == 0 {
+        return Ok(None);
+    }
+    let (apparent_size, disk_size) = file_sizes(metadata, path, opts, ctx);
+    let mtime = metadata.modified().ok();
+    Ok(Some(Node {
+        name,
+        size: if opts.disk_usage { disk_size } else { apparent_size },
+        apparent_size,
+        disk_size,
+        path: path.to_path_buf(),
+        children: Vec::new(),
+        is_dir: false,
+        mtime,
+        newest_mtime: mtime,
+        oldest_mtime: mtime,
+        file_count: 1,
+        ..Default::default()
+    }))
+}
+
+// (по длине, по занятым блокам); повторная жёсткая ссылка не занимает места ни в одном из них
+fn file_sizes(metadata: &fs::Metadata, path: &Path, opts: &ScanOptions, ctx: &ScanContext) -> (u64, u64) {
+    if opts.count_hardlinks_once && is_repeated_hardlink(metadata, path, ctx) {
+        return (0, 0);
+    }
+    (metadata.len(), disk_usage(metadata))
+}
+
+#[cfg(unix)]
+fn disk_usage(metadata: &fs::Metadata) -> u64 {
+    use std::os::unix::fs::MetadataExt;
+    metadata.blocks() * 512
+}
+
+#[cfg(not(unix))]
+fn disk_usage(metadata: &fs::Metadata) -> u64 {
+    metadata.len()
+}
+
+#[cfg(unix)]
+fn is_repeated_hardlink(metadata: &fs::Metadata, path: &Path, ctx: &ScanContext) -> bool {
+    use std::collections::hash_map::Entry;
+    use std::os::unix::fs::MetadataExt;
+    if metadata.nlink() <= 1 {
+        return false;
+    }
+    match ctx.hardlinks.lock().unwrap().entry((metadata.dev(), metadata.ino())) {
+        Entry::Occupied(owner) => owner.get() != path,
+        Entry::Vacant(slot) => {
+            slot.insert(path.to_path_buf());
+            false
+        }
+    }
+}
+
+#[cfg(not(unix))]
+fn is_repeated_hardlink(_metadata: &fs::Metadata, _path: &Path, _ctx: &ScanContext) -> bool {
+    false
+}
+
+#[cfg(windows)]
+fn is_hidden(entry: &fs::DirEntry) -> bool {
+    entry.metadata().is_ok_and(|m| is_hidden_path(&entry.path(), &m))
+}
+
+#[cfg(not(windows))]
+fn is_hidden(entry: &fs::DirEntry) -> bool {
+    entry.file_name().to_string_lossy().starts_with('.')
+}
+
+// то же для записи, у которой нет DirEntry
+#[cfg(windows)]
+fn is_hidden_path(_path: &Path, metadata: &fs::Metadata) -> bool {
+    use std::os::windows::fs::MetadataExt;
+    const FILE_ATTRIBUTE_HIDDEN: u32 = 0x2;
+    metadata.file_attributes() & FILE_ATTRIBUTE_HIDDEN != 0
+}
+
+#[cfg(not(windows))]
+fn is_hidden_path(path: &Path, _metadata: &fs::Metadata) -> bool {
+    path.file_name().is_some_and(|name| name.to_string_lossy().starts_with('.'))
+}
+
+#[cfg(unix)]
+pub fn device_id(metadata: &fs::Metadata) -> Option<u64> {
+    use std::os::unix::fs::MetadataExt;
+    Some(metadata.dev())
+}
+
+#[cfg(not(unix))]
+pub fn device_id(_metadata: &fs::Metadata) -> Option<u64> {
+    None
+}
+
+fn is_other_filesystem(metadata: &fs::Metadata, opts: &ScanOptions) -> bool {
+    opts.root_dev.is_some_and(|root| device_id(metadata).is_some_and(|dev| dev != root))
+}
+
+// точка монтирования другой файловой системы: показываем пустым листом, внутрь не заходим
+fn mount_point_node(path: &Path, metadata: &fs::Metadata) -> Node {
+    Node {
+        name: path.file_name().map_or("".to_string(), |s| s.to_string_lossy().into_owned()),
+        size: 0,
+        path: path.to_path_buf(),
+        children: Vec::new(),
+        is_dir: true,
+        mtime: metadata.modified().ok(),
+        ..Default::default()
+    }
+}
+
+fn build_symlink(path: &Path, opts: &ScanOptions, ctx: &ScanContext, depth: usize, ignores: &[Arc<Gitignore>]) -> Result<Option<Node>> {
+    // битая ссылка попадает в список ошибок, сама не считается
+    ctx.metadata_calls.fetch_add(1, Ordering::Relaxed);
+    let metadata = match fs::metadata(path) {
+        Ok(metadata) => metadata,
+        Err(e) => {
+            record_scan_error(opts, ctx, path, e);
+            return Ok(None);
+        }
+    };
+
+    if metadata.is_dir() && !is_other_filesystem(&metadata, opts) {
+        let target = match fs::canonicalize(path) {
+            Ok(target) => target,
+            Err(e) => {
+                record_scan_error(opts, ctx, path, e);
+                return Ok(None);
+            }
+        };
+        if ctx.visited_dirs.lock().unwrap().contains(&target) {
+            // цикл или уже посчитанная директория
+            return Ok(Some(Node {
+                name: path.file_name().map_or("".to_string(), |s| s.to_string_lossy().into_owned()),
+                size: 0,
+                path: path.to_path_buf(),
+                children: Vec::new(),
+                is_dir: true,
+                mtime: metadata.modified().ok(),
+                ..Default::default()
+            }));
+        }
+    }
+    build_resolved(path, &metadata, opts, ctx, depth, ignores)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::file;
+
+    const TOTAL: u64 = 100_000_000;
+
+    // средний файл мелкий, поэтому порог задаёт доля от размера директории
+    fn threshold(file_count: u64) -> u64 {
+        compute_other_threshold(file_count, TOTAL, file_count * 1000, OTHER_TIER_FACTORS)
+    }
+
+    #[test]
+    fn other_threshold_tiers() {
+        assert_eq!(threshold(1), 1_000_000);
+        assert_eq!(threshold(50), 1_000_000);
+        assert_eq!(threshold(51), 500_000);
+        assert_eq!(threshold(200), 500_000);
+        assert_eq!(threshold(201), 100_000);
+        assert_eq!(threshold(100_000), 100_000);
+    }
+
+    #[test]
+    fn other_threshold_custom_factors() {
+        let factors = [0.1, 0.05, 0.02];
+        assert_eq!(compute_other_threshold(50, TOTAL, 50_000, factors), 10_000_000);
+        assert_eq!(compute_other_threshold(51, TOTAL, 51_000, factors), 5_000_000);
+        assert_eq!(compute_other_threshold(201, TOTAL, 201_000, factors), 2_000_000);
+    }
+
+    #[test]
+    fn other_threshold_without_files() {
+        assert_eq!(compute_other_threshold(0, TOTAL, 0, OTHER_TIER_FACTORS), u64::MAX);
+        assert_eq!(compute_other_threshold(0, 0, 0, OTHER_TIER_FACTORS), u64::MAX);
+    }
+
+    #[test]
+    fn other_threshold_floor() {
+        assert_eq!(compute_other_threshold(10, 1000, 1000, OTHER_TIER_FACTORS), 1024);
+        assert_eq!(compute_other_threshold(3, 0, 0, OTHER_TIER_FACTORS), 1024);
+    }
+
+    #[test]
+    fn other_threshold_single_huge_file() {
+        let size = 10_000_000_000;
+        let threshold = compute_other_threshold(1, size, size, OTHER_TIER_FACTORS);
+        // пятая часть среднего файла больше доли директории, но сам файл в "Прочее" не уходит
+        assert_eq!(threshold, size / 5);
+        assert!(threshold < size);
+    }
+
+    #[test]
+    fn other_threshold_average_file_dominates() {
+        // 10 файлов по 1 МБ: пятая часть среднего больше 1% директории
+        assert_eq!(compute_other_threshold(10, 10_000_000, 10_000_000, OTHER_TIER_FACTORS), 200_000);
+    }
+
+    #[test]
+    fn other_bucket_is_not_counted_as_dir() {
+        let mut children = vec![file("big", 1_000_000)];
+        children.extend((0..30).map(|i| file(&format!("s{}", i), 10)));
+        let root = assemble_dir(Path::new("/t"), children, false, &ScanOptions::default(), 0);
+        assert!(root.children.iter().any(|c| c.is_aggregated));
+        assert_eq!(root.entry_counts(), (31, 1));
+    }
+
+    #[test]
+    fn regroup_other_follows_disk_size() {
+        let sized = |name: &str, apparent_size: u64, disk_size: u64| Node {
+            size: apparent_size,
+            apparent_size,
+            disk_size,
+            ..file(name, apparent_size)
+        };
+        let mut children = vec![sized("big", 1_000_000, 1_000_000), sized("sparse", 100, 400_000)];
+        children.extend((0..30).map(|i| sized(&format!("s{}", i), 10, 4096)));
+        let opts = ScanOptions::default();
+        let mut root = assemble_dir(Path::new("/t"), children, false, &opts, 0);
+        let other = |root: &Node| root.children.iter().find(|c| c.is_aggregated).cloned().unwrap();
+        assert!(other(&root).children.iter().any(|c| c.name == "sparse"));
+
+        root.use_disk_size(true);
+        regroup_other(&mut root, &opts, 0);
+        assert_eq!(root.size, 1_000_000 + 400_000 + 30 * 4096);
+        assert!(root.children.iter().any(|c| c.name == "sparse" && !c.is_aggregated));
+        assert_eq!(other(&root).size, 30 * 4096);
+        assert_eq!(other(&root).file_count, 30);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn unreadable_dir_is_incomplete() {
+        use std::os::unix::fs::PermissionsExt;
+        let root = std::env::temp_dir().join(format!("volume-inspector-unreadable-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        let locked = root.join("locked");
+        fs::create_dir_all(&locked).unwrap();
+        fs::set_permissions(&locked, fs::Permissions::from_mode(0o000)).unwrap();
+        // под root права не мешают чтению, проверять нечего
+        if fs::read_dir(&locked).is_ok() {
+            fs::set_permissions(&locked, fs::Permissions::from_mode(0o755)).unwrap();
+            fs::remove_dir_all(&root).unwrap();
+            eprintln!("unreadable_dir_is_incomplete пропущен: директория без прав всё равно читается (запуск от root?)");
+            return;
+        }
+
+        let opts = ScanOptions { scan_root: root.clone(), ..Default::default() };
+        let ctx = ScanContext::default();
+        let tree = scan(&root, &opts, &ctx).unwrap();
+        fs::set_permissions(&locked, fs::Permissions::from_mode(0o755)).unwrap();
+        fs::remove_dir_all(&root).unwrap();
+
+        assert!(ctx.errors.lock().unwrap().iter().any(|(path, _)| *path == locked));
+        let node = tree.children.iter().find(|c| c.name == "locked").expect("нечитаемая директория в дереве");
+        assert!(node.incomplete);
+        assert!(tree.incomplete);
+    }
+
+    // a/data и b/link — одна жёсткая ссылка; пересканированное поддерево не должно посчитать её второй раз
+    #[cfg(unix)]
+    #[test]
+    fn subtree_rescan_keeps_hardlink_counted_once() {
+        let root = std::env::temp_dir().join(format!("volume-inspector-hardlinks-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(root.join("a")).unwrap();
+        fs::create_dir_all(root.join("b")).unwrap();
+        fs::write(root.join("a").join("data"), vec![0u8; 4096]).unwrap();
+        fs::hard_link(root.join("a").join("data"), root.join("b").join("link")).unwrap();
+
+        let opts = ScanOptions {
+            count_hardlinks_once: true,
+            no_other: true,
+            scan_root: root.clone(),
+            ..Default::default()
+        };
+        let ctx = ScanContext::default();
+        let tree = scan(&root, &opts, &ctx).unwrap();
+        assert_eq!(tree.size, 4096);
+        let hardlinks = ctx.take_hardlinks();
+        let size_of = |name: &str| tree.children.iter().find(|c| c.name == name).map_or(0, |c| c.size);
+
+        // какая из копий посчитана первой, решает планировщик; вместе с остальным деревом поддерево даёт размер файла
+        for (subtree, other) in [("a", "b"), ("b", "a")] {
+            let path = root.join(subtree);
+            let ctx = ScanContext::for_subtree(hardlinks.clone(), &path);
+            let rescanned = scan(&path, &opts, &ctx).unwrap();
+            assert_eq!(rescanned.size + size_of(other), 4096, "пересканирован {}", subtree);
+        }
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
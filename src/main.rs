@@ -1,85 +1,657 @@
 use anyhow::Result;
-use clap::Parser;
+use clap::{CommandFactory, Parser, ValueEnum, ValueHint};
 use crossterm::{
     event::{self, Event, KeyCode, KeyEventKind, MouseEventKind, EnableMouseCapture, DisableMouseCapture},
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
     ExecutableCommand,
 };
-use std::sync::atomic::{AtomicU64, AtomicBool, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use volume_inspector::*;
+use volume_inspector::analysis::*;
+use volume_inspector::cache::{load_cache, load_cache_header, save_cache};
+use volume_inspector::formats::*;
+use volume_inspector::treemap::{layout_squarified, layout_tree};
 
 use crossterm::event::KeyModifiers;
 
 use ratatui::{
     backend::CrosstermBackend,
-    layout::{Constraint, Direction, Layout, Rect},
-    style::{Color, Style},
-    text::Line,
-    widgets::{Block, Borders, Paragraph},
+    layout::{Constraint, Direction, Layout, Margin, Rect},
+    style::{Color, Modifier, Style},
+    symbols,
+    text::{Line, Span},
+    widgets::{Block, Borders, Clear, Paragraph, Sparkline},
     Frame, Terminal,
 };
+use std::borrow::Cow;
 use std::fs;
-use std::io::stdout;
+use std::io::{stdout, BufReader, BufWriter, Stdout, Write};
 use std::path::{Path, PathBuf};
-use humansize::{SizeFormatter, DECIMAL};
-use std::collections::HashMap;
-use once_cell::sync::Lazy;
+use humansize::{SizeFormatter, BINARY, DECIMAL};
+use std::collections::{HashMap, HashSet, VecDeque};
+use once_cell::sync::{Lazy, OnceCell};
 use seahash::hash;
 use std::sync::Mutex;
 use url::Url;
+use std::sync::Arc;
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use notify::event::ModifyKind;
+use notify::{EventKind, RecursiveMode, Watcher};
+
+const BREADCRUMB_SEPARATOR: &str = " › ";
+
+// сколько последних пересканирований помнить для графика в статусе
+const SIZE_HISTORY_LEN: usize = 20;
+
+// --watch: сколько ждать тишины после последнего события файловой системы
+const WATCH_DEBOUNCE: Duration = Duration::from_millis(500);
+// и сколько самое большее ждать от первого события, если файл пишется без перерыва
+const WATCH_MAX_WAIT: Duration = Duration::from_secs(2);
+// увеличение плитки при входе в директорию и пауза между его кадрами
+const ZOOM_DURATION: Duration = Duration::from_millis(180);
+const ZOOM_FRAME: Duration = Duration::from_millis(16);
+
+// пунктирная рамка для директорий, сканирование которых было прервано
+const DASHED_BORDER: symbols::border::Set = symbols::border::Set {
+    top_left: "╭",
+    top_right: "╮",
+    bottom_left: "╰",
+    bottom_right: "╯",
+    vertical_left: "┆",
+    vertical_right: "┆",
+    horizontal_top: "┄",
+    horizontal_bottom: "┄",
+};
 
-static SCANNED_FILES: AtomicU64 = AtomicU64::new(0);
-static CURRENT_PATH: Lazy<Mutex<String>> = Lazy::new(|| Mutex::new(String::new()));
+// единый список клавиш для справки (?), держать в синхроне с обработчиками в main
+fn keybindings() -> Vec<(&'static str, &'static str)> {
+    vec![
+        ("q / Esc", tr("выход", "quit")),
+        (tr("Enter / клик", "Enter / click"), tr("войти в директорию", "enter directory")),
+        ("Backspace", tr("на уровень выше", "go up one level")),
+        ("Home", tr("вернуться в корень сканирования", "back to the scan root")),
+        (tr("клик по пути сверху", "click on top path"), tr("перейти к директории", "go to directory")),
+        ("← ↑ ↓ → / h j k l", tr("выбор плитки (прокрутка в режиме прокрутки)", "select tile (pan in scroll mode)")),
+        ("H J K L", tr("быстрая прокрутка", "fast pan")),
+        (tr("колесо / Shift+колесо", "wheel / Shift+wheel"), tr("прокрутка по вертикали / горизонтали", "pan vertically / horizontally")),
+        ("0", tr("вернуться в начало (режим прокрутки)", "reset view (scroll mode)")),
+        ("z", tr("центрировать на выбранной плитке (режим прокрутки)", "center on selected tile (scroll mode)")),
+        (tr("клик по миникарте", "click on minimap"), tr("перейти к этому месту холста (режим прокрутки)", "jump to that part of the canvas (scroll mode)")),
+        ("/", tr("поиск по имени", "search by name")),
+        ("n", tr("следующее совпадение", "next match")),
+        ("s", tr("сменить сортировку", "change sort order")),
+        ("f", tr("папки перед файлами", "folders before files")),
+        ("u", tr("размер файлов / место на диске", "apparent size / disk usage")),
+        ("r", tr("пересканировать текущую директорию", "rescan current directory")),
+        ("t", tr("цвет по типу / возрасту / изменению (--compare)", "color by type / age / change (--compare)")),
+        ("d", tr("удалить выбранное", "delete selected")),
+        ("y", tr("скопировать путь", "copy path")),
+        ("e", tr("легенда цветов по расширениям", "extension color legend")),
+        ("b", tr("группировать файлы по расширению", "group files by extension")),
+        (".", tr("свернуть цепочки одиночных папок a/b/c в одну плитку", "collapse single-child folder chains a/b/c into one tile")),
+        ("p", tr("имена / пути в подписях плиток", "names / paths in tile labels")),
+        ("B", tr("плитки с рамками / сплошной заливкой", "tiles with borders / solid fill")),
+        ("v", tr("список по размеру вместо карты (1-9 — войти, ↑/↓ — выбор)", "ranked list instead of the map (1-9 to enter, ↑/↓ to select)")),
+        ("F / D", tr("только файлы / только папки", "files only / folders only")),
+        ("+ / -", tr("больше / меньше уровней вложенности на экране", "show more / fewer nested levels")),
+        ("a", tr("скрыть / показать скрытые файлы", "hide / show hidden files")),
+        ("o", tr("открыть файл ($EDITOR или приложение по умолчанию)", "open file ($EDITOR or default app)")),
+        (tr("Ctrl+клик", "Ctrl+click"), tr("показать в файловом менеджере", "reveal in file manager")),
+        ("c", tr("открыть терминал в выбранной директории", "open a terminal in the selected directory")),
+        ("i", tr("подробности о выбранном: права, владелец, даты", "details of the selection: mode, owner, dates")),
+        ("E", tr("пути, которые не удалось прочитать", "paths that could not be read")),
+        ("g", tr("гистограмма размеров файлов текущей директории", "file size histogram of the current directory")),
+        ("?", tr("эта справка", "this help")),
+    ]
+}
 
+fn sort_label(mode: SortMode) -> &'static str {
+    match mode {
+        SortMode::Size => tr("размер", "size"),
+        SortMode::Name => tr("имя", "name"),
+        SortMode::Count => tr("количество", "count"),
+        SortMode::Mtime => tr("дата изменения", "modified"),
+    }
+}
 
+static CURRENT_PATH: Lazy<Mutex<String>> = Lazy::new(|| Mutex::new(String::new()));
+// глубина директории из CURRENT_PATH относительно корня сканирования
+static CURRENT_DEPTH: AtomicUsize = AtomicUsize::new(0);
 #[derive(Parser)]
 struct Args {
-    #[arg(default_value = ".")]
+    #[arg(default_value = ".", value_hint = ValueHint::DirPath)]
     path: PathBuf,
 
+    /// напечатать в stdout скрипт автодополнения для оболочки и выйти
+    #[arg(long, value_name = "SHELL", value_enum, exclusive = true)]
+    generate_completions: Option<clap_complete::Shell>,
+
+    /// не показывать ошибки доступа (EACCES) и файлы, исчезнувшие во время сканирования
+    #[arg(long, alias = "ignoreos5")]
+    ignore_permission_errors: bool,
+
+    #[arg(long)]
+    threads: Option<usize>,
+
+    #[arg(long)]
+    max_depth: Option<usize>,
+
+    #[arg(long)]
+    follow_symlinks: bool,
+
+    #[arg(long)]
+    count_hardlinks_once: bool,
+
+    #[arg(long)]
+    disk_usage: bool,
+
+    #[arg(short = 'x', long, alias = "exclude-filesystem")]
+    one_file_system: bool,
+
+    #[arg(long)]
+    exclude_hidden: bool,
+
+    /// учитывать только файлы, чьё имя подходит под регулярное выражение, например '\.log$'
+    #[arg(long, value_name = "REGEX")]
+    name_filter: Option<regex::Regex>,
+
+    /// не включать в дерево пустые файлы и директории нулевого размера (и не считать их)
+    #[arg(long)]
+    exclude_empty: bool,
+
+    #[arg(long, value_enum, default_value_t = LayoutAlgorithm::Slice)]
+    layout: LayoutAlgorithm,
+
+    #[arg(long)]
+    save_cache: Option<PathBuf>,
+
+    #[arg(long)]
+    load_cache: Option<PathBuf>,
+
+    #[arg(long, value_name = "FILE")]
+    export_ncdu: Option<PathBuf>,
+
+    /// сводка по расширениям: extension,total_bytes,file_count, крупные сверху
+    #[arg(long, value_name = "FILE")]
+    export_csv: Option<PathBuf>,
+
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["load_cache", "import_du"])]
+    import_ncdu: Option<PathBuf>,
+
+    /// сравнить два кэша (--save-cache): дерево берётся из второго, цвет показывает изменение
+    #[arg(long, num_args = 2, value_names = ["BEFORE", "AFTER"], conflicts_with_all = ["load_cache", "import_ncdu", "import_du"])]
+    compare: Option<Vec<PathBuf>>,
+
+    /// вывод `du -ab` (размер<TAB>путь), иерархия восстанавливается по путям
+    #[arg(long, value_name = "FILE", conflicts_with = "load_cache")]
+    import_du: Option<PathBuf>,
+
+    #[arg(long, value_parser = parse_size)]
+    min_size: Option<u64>,
+
+    /// бюджет размера: плитки больше него обведены красным и помечены "!"; с --print-tree превышение даёт код выхода 1
+    #[arg(long, value_name = "SIZE", value_parser = parse_size)]
+    warn_size: Option<u64>,
+
+    #[arg(long, conflicts_with = "min_size")]
+    no_other: bool,
+
+    /// доли размера директории для порога "Прочее": до 50 файлов, до 200, больше (по умолчанию 0.01,0.005,0.001)
+    #[arg(long, value_name = "SMALL,MEDIUM,LARGE", value_parser = parse_factors, conflicts_with_all = ["min_size", "no_other"])]
+    other_factors: Option<[f64; 3]>,
+
+    #[arg(long)]
+    dirs_first: bool,
+
+    #[arg(long)]
+    respect_gitignore: bool,
+
+    /// glob-шаблон исключения (можно повторять), сопоставляется с путём относительно корня сканирования
+    #[arg(long, value_name = "GLOB")]
+    exclude: Vec<String>,
+
+    /// файл с glob-шаблонами исключения, по одному на строку; строки с '#' — комментарии
+    #[arg(long, value_name = "FILE", value_hint = ValueHint::FilePath)]
+    exclude_from: Option<PathBuf>,
+
+    #[arg(long, default_value_t = 3, value_parser = clap::value_parser!(u16).range(1..))]
+    min_tile: u16,
+
+    /// сколько уровней вложенности рисовать; глубже директории остаются сплошными плитками
+    #[arg(long, value_name = "N", value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..))]
+    render_depth: Option<usize>,
+
+    #[arg(long, value_enum, default_value_t = Lang::Ru)]
+    lang: Lang,
+
+    #[arg(long, value_enum, default_value_t = Units::Decimal)]
+    units: Units,
+
+    #[arg(long, value_name = "FILE")]
+    theme: Option<PathBuf>,
+
+    /// палитра терминала; auto смотрит на COLORTERM и TERM, RGB-цвета сводятся к ближайшим из 256 или 16
+    #[arg(long, value_enum, default_value_t = ColorSupport::Auto)]
+    color: ColorSupport,
+
+    #[arg(long, alias = "read-only")]
+    no_delete: bool,
+
+    /// без значков 📁/📄 в подписях плиток (для терминалов без эмодзи)
+    #[arg(long)]
+    no_icons: bool,
+
+    /// плитки без рамок, сплошной заливкой: помещается больше мелких плиток
+    #[arg(long)]
+    dense: bool,
+
+    /// без цвета: плитки различаются штриховкой, выбранная обведена двойной рамкой; то же при заданной NO_COLOR
+    #[arg(long, conflicts_with = "color")]
+    no_color: bool,
+
+    /// корень-симлинк сканировать по цели, но показывать под введённым путём
+    #[arg(long)]
+    follow_root_only: bool,
+
+    /// имя корня сканирования в интерфейсе вместо его пути; операции по-прежнему идут по настоящему пути
+    #[arg(long, value_name = "NAME")]
+    root_label: Option<String>,
+
+    /// входить в директорию сразу, без анимации увеличения плитки
+    #[arg(long)]
+    no_animation: bool,
+
+    /// как часто (мс) проверять изменения от --watch, пока нет ввода
+    #[arg(long, value_name = "MS", default_value_t = 250, value_parser = clap::value_parser!(u64).range(10..))]
+    redraw_interval: u64,
+
+    /// следить за изменениями в открытой директории и обновлять дерево на лету
+    #[arg(long, conflicts_with_all = ["import_ncdu", "import_du", "compare"])]
+    watch: bool,
+
+    /// после выхода напечатать последнюю открытую директорию (для cd из обёртки в shell)
     #[arg(long)]
-    ignoreos5: bool,
+    print_cwd_on_exit: bool,
+
+    #[arg(long)]
+    print_tree: bool,
+
+    /// после сканирования вывести в stderr число вызовов read_dir/metadata и самые медленные директории
+    #[arg(long)]
+    profile: bool,
+
+    /// не печатать строку прогресса и итоги сканирования, чтобы stdout остался чистым для --print-tree, --top и т.п.
+    #[arg(short, long)]
+    quiet: bool,
+
+    #[arg(long, requires = "print_tree")]
+    depth: Option<usize>,
+
+    /// найти одинаковые файлы (тот же размер и хэш содержимого), напечатать группы и выйти
+    #[arg(long, conflicts_with_all = ["print_tree", "top"])]
+    find_dupes: bool,
+
+    /// напечатать N самых больших файлов всего дерева и выйти
+    #[arg(long, value_name = "N", conflicts_with = "print_tree", value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..))]
+    top: Option<usize>,
+}
+
+fn parse_size(s: &str) -> Result<u64, String> {
+    let s = s.trim();
+    let digits_end = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    let (number, suffix) = s.split_at(digits_end);
+    let number: u64 = number
+        .parse()
+        .map_err(|_| format!("некорректный размер '{}', ожидается число с суффиксом K/M/G/T", s))?;
+    let multiplier: u64 = match suffix.trim().to_uppercase().trim_end_matches("IB").trim_end_matches('B') {
+        "" => 1,
+        "K" => 1 << 10,
+        "M" => 1 << 20,
+        "G" => 1 << 30,
+        "T" => 1 << 40,
+        _ => return Err(format!("неизвестный суффикс '{}' в размере '{}'", suffix, s)),
+    };
+    number
+        .checked_mul(multiplier)
+        .ok_or_else(|| format!("слишком большой размер '{}'", s))
+}
+
+fn parse_factors(s: &str) -> Result<[f64; 3], String> {
+    let mut factors = [0.0; 3];
+    let parts: Vec<&str> = s.split(',').collect();
+    if parts.len() != 3 {
+        return Err(format!("ожидается три доли через запятую, получено '{}'", s));
+    }
+    for (factor, part) in factors.iter_mut().zip(parts) {
+        *factor = match part.trim().parse::<f64>() {
+            Ok(value) if (0.0..=1.0).contains(&value) => value,
+            _ => return Err(format!("некорректная доля '{}', ожидается число от 0 до 1", part)),
+        };
+    }
+    Ok(factors)
 }
 
+// как у rsync --exclude-from: шаблон на строку, пустые строки и комментарии '#' пропускаются
+fn read_exclude_file(file: &Path) -> Result<Vec<String>> {
+    let text = fs::read_to_string(file).map_err(|e| anyhow::anyhow!("{}: {}", file.display(), e))?;
+    Ok(text.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(str::to_string)
+        .collect())
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum LayoutAlgorithm {
+    Slice,
+    // направление разреза по длинной стороне прямоугольника, а не по очереди
+    SliceLongest,
+    Squarified,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Lang {
+    Ru,
+    En,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Units {
+    Decimal,
+    Binary,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum ColorSupport {
+    Auto,
+    #[value(name = "16")]
+    Ansi16,
+    #[value(name = "256")]
+    Ansi256,
+    Truecolor,
+    // --no-color и NO_COLOR
+    #[value(skip)]
+    Monochrome,
+}
+
+// то, что нужно для отрисовки плитки, без поддерева: раскладка не клонирует детей
 #[derive(Clone)]
-struct Node {
+struct TileInfo {
     name: String,
     size: u64,
     path: PathBuf,
-    children: Vec<Node>,
     is_dir: bool,
+    has_children: bool,
+    mtime: Option<SystemTime>,
+    incomplete: bool,
+    is_aggregated: bool,
+    // группа файлов одного расширения в режиме 'b'
+    is_bucket: bool,
+    file_count: u64,
+    dir_count: u64,
+}
+
+impl From<&Node> for TileInfo {
+    fn from(node: &Node) -> Self {
+        TileInfo {
+            name: node.name.clone(),
+            size: node.size,
+            path: node.path.clone(),
+            is_dir: node.is_dir,
+            has_children: !node.children.is_empty(),
+            mtime: node.mtime,
+            incomplete: node.incomplete,
+            is_aggregated: node.is_aggregated,
+            is_bucket: false,
+            file_count: node.file_count,
+            dir_count: node.dir_count,
+        }
+    }
+}
+
+// вход в директорию: плитка, которая сейчас растёт до размеров холста
+struct ZoomAnimation {
+    from: Rect,
+    color: Color,
+    name: String,
+    start: Instant,
+}
+
+// пересчитывает диапазон mtime у предков path снизу вверх, после того как поддерево заменено
+fn refresh_mtime_range(node: &mut Node, path: &Path) {
+    if node.path == path {
+        return;
+    }
+    if let Some(child) = node.children.iter_mut().find(|c| c.is_dir && path.starts_with(&c.path)) {
+        refresh_mtime_range(child, path);
+    }
+    (node.newest_mtime, node.oldest_mtime) = mtime_range(&node.children);
 }
 
-impl Node {
-    fn total_size(&self) -> u64 {
-        self.size
+// файлы директории сводятся в узлы по расширению, поддиректории остаются плитками без вложенности
+fn group_by_extension(node: &Node, mode: SortMode, dirs_first: bool) -> Node {
+    let mut buckets: HashMap<String, Node> = HashMap::new();
+    let mut children = Vec::new();
+    for child in &node.children {
+        if child.is_dir || child.is_aggregated {
+            children.push(child.with_children(Vec::new()));
+            continue;
+        }
+        let name = match child.path.extension() {
+            Some(ext) => format!("*.{}", ext.to_string_lossy().to_lowercase()),
+            None => "*".to_string(),
+        };
+        let bucket = buckets.entry(name.clone()).or_insert_with(|| Node {
+            path: synthetic_path(&node.path, &name),
+            name,
+            is_aggregated: true,
+            ..Default::default()
+        });
+        bucket.size += child.size;
+        bucket.apparent_size += child.apparent_size;
+        bucket.disk_size += child.disk_size;
+        bucket.file_count += 1;
+        bucket.mtime = bucket.mtime.max(child.mtime);
+        bucket.newest_mtime = bucket.newest_mtime.max(child.newest_mtime);
+        bucket.oldest_mtime = bucket.oldest_mtime.into_iter().chain(child.oldest_mtime).min();
+    }
+    children.extend(buckets.into_values());
+    sort_nodes(&mut children, mode, dirs_first);
+
+    Node {
+        is_dir: true,
+        is_aggregated: false,
+        threshold: 0,
+        ..node.with_children(children)
     }
 }
 
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum ViewFilter {
+    All,
+    // 'F': только файлы текущей директории
+    Files,
+    // 'D': только директории на всех уровнях
+    Dirs,
+}
+
+// '.': цепочка директорий с единственной поддиректорией a/b/c/d становится одной плиткой "a/b/c/d";
+// плитка ведёт в последнюю директорию цепочки, размеры не меняются
+fn collapse_chains(node: &Node) -> Node {
+    let children = node.children.iter()
+        .map(|child| {
+            let mut name = child.name.clone();
+            let mut tail = child;
+            while tail.is_dir && !tail.is_aggregated && tail.children.len() == 1 {
+                let next = &tail.children[0];
+                if !next.is_dir || next.is_aggregated {
+                    break;
+                }
+                name = format!("{}/{}", name, next.name);
+                tail = next;
+            }
+            let mut collapsed = collapse_chains(tail);
+            collapsed.name = name;
+            collapsed
+        })
+        .collect();
+    node.with_children(children)
+}
+
+// скрытое фильтром собирается в одну серую плитку на каждом уровне, чтобы пропорции не поплыли
+fn filter_view(node: &Node, filter: ViewFilter) -> Node {
+    let mut children = Vec::new();
+    // путь не зависит от языка интерфейса и не совпадёт с настоящим файлом "(files)"
+    let (name, key) = match filter {
+        ViewFilter::Dirs => (tr("(файлы)", "(files)"), "(files)"),
+        _ => (tr("(папки)", "(folders)"), "(folders)"),
+    };
+    let mut hidden = Node {
+        name: name.to_string(),
+        path: synthetic_path(&node.path, key),
+        is_aggregated: true,
+        ..Default::default()
+    };
+    for child in &node.children {
+        // "Прочее" — это файлы, хоть и выглядит как директория
+        let is_dir = child.is_dir && !child.is_aggregated;
+        match filter {
+            ViewFilter::Dirs if is_dir => children.push(filter_view(child, filter)),
+            ViewFilter::Files if !is_dir => children.push(child.clone()),
+            ViewFilter::All => children.push(child.clone()),
+            _ => {
+                let (files, dirs) = child.entry_counts();
+                hidden.size += child.size;
+                hidden.apparent_size += child.apparent_size;
+                hidden.disk_size += child.disk_size;
+                hidden.newest_mtime = hidden.newest_mtime.max(child.newest_mtime);
+                hidden.oldest_mtime = hidden.oldest_mtime.into_iter().chain(child.oldest_mtime).min();
+                hidden.file_count += files;
+                hidden.dir_count += dirs;
+            }
+        }
+    }
+    // директория без поддиректорий в режиме 'D' остаётся сплошной плиткой, а не плиткой "(файлы)"
+    if hidden.size > 0 && !(filter == ViewFilter::Dirs && children.is_empty()) {
+        children.push(hidden);
+    }
+
+    node.with_children(children)
+}
 
 fn open_in_file_manager(path: &Path) {
     #[cfg(target_os = "windows")]
     {
-
-        if let Ok(url) = Url::from_file_path(path) {
-
+        // в file:// нет места для \\?\, Проводнику отдаём обычную запись пути
+        if let Ok(url) = Url::from_file_path(display_path(path)) {
             let _ = showfile::show_uri_in_file_manager(url.as_str());
         }
     }
 
     #[cfg(not(target_os = "windows"))]
     {
-
         if let Ok(url) = Url::from_file_path(path) {
             let _ = showfile::show_uri_in_file_manager(url.as_str());
         }
     }
 }
 
+// обратное к long_path: то, что показываем людям и отдаём другим программам
+#[cfg(windows)]
+fn display_path(path: &Path) -> Cow<'_, Path> {
+    let Some(text) = path.to_str() else {
+        return Cow::Borrowed(path);
+    };
+    if let Some(rest) = text.strip_prefix(r"\\?\UNC\") {
+        Cow::Owned(PathBuf::from(format!(r"\\{}", rest)))
+    } else if let Some(rest) = text.strip_prefix(r"\\?\")
+        && rest.as_bytes().get(1) == Some(&b':')
+    {
+        Cow::Owned(PathBuf::from(rest))
+    } else {
+        Cow::Borrowed(path)
+    }
+}
+
+#[cfg(not(windows))]
+fn display_path(path: &Path) -> Cow<'_, Path> {
+    Cow::Borrowed(path)
+}
+
+// (всего, свободно) на файловой системе, куда смонтирован путь
+fn filesystem_space(path: &Path) -> Option<(u64, u64)> {
+    let disks = sysinfo::Disks::new_with_refreshed_list();
+    disks.list().iter()
+        .filter(|d| path.starts_with(d.mount_point()))
+        .max_by_key(|d| d.mount_point().as_os_str().len())
+        .map(|d| (d.total_space(), d.available_space()))
+}
+
+// буфер обмена держим живым всю сессию: на X11 содержимое пропадает вместе с владельцем
+#[cfg(feature = "clipboard")]
+static CLIPBOARD: Lazy<Mutex<Option<arboard::Clipboard>>> =
+    Lazy::new(|| Mutex::new(arboard::Clipboard::new().ok()));
+
+#[cfg(feature = "clipboard")]
+fn copy_to_clipboard(text: &str) -> Result<()> {
+    let mut clipboard = CLIPBOARD.lock().unwrap();
+    let clipboard = clipboard
+        .as_mut()
+        .ok_or_else(|| anyhow::anyhow!(tr("буфер обмена недоступен", "clipboard is unavailable")))?;
+    clipboard.set_text(text)?;
+    Ok(())
+}
 
+#[cfg(not(feature = "clipboard"))]
+fn copy_to_clipboard(_text: &str) -> Result<()> {
+    anyhow::bail!(tr("сборка без поддержки буфера обмена", "built without clipboard support"))
+}
 
 static COLOR_CACHE: Lazy<std::sync::Mutex<HashMap<String, Color>>> = 
     Lazy::new(|| std::sync::Mutex::new(HashMap::new()));
 
+// палитра, к которой сводятся RGB-цвета перед выводом; не задана — выводим как есть
+static COLOR_SUPPORT: OnceCell<ColorSupport> = OnceCell::new();
+
+// цвета из --theme; всё, чего нет в файле, считается как раньше
+static THEME: OnceCell<Theme> = OnceCell::new();
+
+#[derive(Default)]
+struct Theme {
+    directory: Option<Color>,
+    other: Option<Color>,
+    selection: Option<Color>,
+    extensions: HashMap<String, Color>,
+}
+
+// цвета в файле — "#rrggbb" или имена вроде "lightblue"
+#[derive(Deserialize)]
+struct ThemeFile {
+    directory: Option<String>,
+    other: Option<String>,
+    selection: Option<String>,
+    #[serde(default)]
+    extensions: HashMap<String, String>,
+}
+
+fn load_theme(file: &Path) -> Result<Theme> {
+    let parsed: ThemeFile = toml::from_str(&fs::read_to_string(file)?)?;
+    let color = |value: &str| -> Result<Color> {
+        value.parse().map_err(|_| anyhow::anyhow!("{}: {}", tr("некорректный цвет", "invalid color"), value))
+    };
+    let mut extensions = HashMap::new();
+    for (ext, value) in &parsed.extensions {
+        extensions.insert(ext.trim_start_matches('.').to_lowercase(), color(value)?);
+    }
+    Ok(Theme {
+        directory: parsed.directory.as_deref().map(color).transpose()?,
+        other: parsed.other.as_deref().map(color).transpose()?,
+        selection: parsed.selection.as_deref().map(color).transpose()?,
+        extensions,
+    })
+}
+
 fn color_for_extension(ext: Option<&str>) -> Color {
     let ext = ext.unwrap_or("").to_lowercase();
     if ext.is_empty() {
@@ -87,6 +659,10 @@ fn color_for_extension(ext: Option<&str>) -> Color {
     }
 
 
+    if let Some(color) = THEME.get().and_then(|t| t.extensions.get(&ext)) {
+        return *color;
+    }
+
     {
         let cache = COLOR_CACHE.lock().unwrap();
         if let Some(color) = cache.get(&ext) {
@@ -139,268 +715,276 @@ fn hsl_to_rgb(h: f64, s: f64, l: f64) -> (u8, u8, u8) {
     (r, g, b)
 }
 
-fn dynamic_color(node: &Node, total_size: u64, is_other: bool) -> Color {
-    if total_size == 0 {
-        return Color::DarkGray;
-    }
-    
-    let norm = (node.size as f64 / total_size as f64).sqrt();
-    let brightness = (90.0 + 165.0 * norm) as u8;
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum ColorMode {
+    Extension,
+    Age,
+    Delta,
+}
 
-    if is_other {
-        let gray = brightness.saturating_sub(30).clamp(60, 180);
-        return Color::Rgb(gray, gray, gray);
+// рост между двумя снимками — красным, уменьшение — зелёным, насыщенность по величине изменения
+fn delta_color(before: u64, after: u64, max_delta: u64) -> Color {
+    if before == after || max_delta == 0 {
+        return Color::Rgb(70, 70, 80);
     }
-
-    if node.is_dir {
-        let r = (brightness / 4) as u8;
-        let g = (brightness * 2 / 3) as u8;
-        let b = (brightness * 3 / 4 + 40) as u8;
-        return Color::Rgb(r.clamp(30, 120), g.clamp(100, 220), b.clamp(120, 255));
+    let t = ((after.abs_diff(before) as f64 / max_delta as f64).sqrt()).clamp(0.0, 1.0);
+    let lerp = |a: u8, b: u8| (a as f64 + (b as f64 - a as f64) * t) as u8;
+    if after > before {
+        Color::Rgb(lerp(90, 210), lerp(70, 40), lerp(75, 40))
+    } else {
+        Color::Rgb(lerp(70, 40), lerp(90, 180), lerp(75, 60))
     }
+}
 
-    let base = color_for_extension(node.path.extension().and_then(|s| s.to_str()));
-    if let Color::Rgb(r, g, b) = base {
-        let factor = 0.6 + norm * 0.8;
-        let avg = (r as f64 + g as f64 + b as f64) / 3.0;
-        
-        let r_new = (r as f64 + (r as f64 - avg) * factor).clamp(60.0, 255.0) as u8;
-        let g_new = (g as f64 + (g as f64 - avg) * factor).clamp(60.0, 255.0) as u8;
-        let b_new = (b as f64 + (b as f64 - avg) * factor).clamp(60.0, 255.0) as u8;
-        
-        Color::Rgb(r_new, g_new, b_new)
+fn format_delta(before: u64, after: u64, units: Units) -> String {
+    if after >= before {
+        format!("+{}", format_size(after - before, units))
     } else {
-        Color::Rgb(brightness, brightness, brightness)
+        format!("−{}", format_size(before - after, units))
     }
 }
 
-fn build_tree(root: &Path, ignore_os5: bool) -> Result<Node> {
-    {
-        let mut p = CURRENT_PATH.lock().unwrap();
-        *p = root.display().to_string();
+// размеры из старого снимка; у "Прочее" свой путь с OTHER_MARKER, поэтому с родителем он не совпадает
+fn collect_sizes(node: &Node, sizes: &mut HashMap<PathBuf, u64>) {
+    sizes.insert(node.path.clone(), node.size);
+    for child in &node.children {
+        collect_sizes(child, sizes);
     }
-    let mut children = Vec::new();
-    let mut total_size = 0u64;
-    let mut file_count = 0;
-    let mut file_total_size = 0u64;
+}
 
-    let read_dir = match fs::read_dir(root) {
-        Ok(rd) => rd,
-        Err(e) => {
-            if ignore_os5 && e.kind() == std::io::ErrorKind::PermissionDenied {
-                return Ok(Node {
-                    name: root.file_name().map_or("".to_string(), |s| s.to_string_lossy().into_owned()),
-                    size: 0,
-                    path: root.to_path_buf(),
-                    children: Vec::new(),
-                    is_dir: true,
-                });
-            } else {
-                return Err(e.into());
-            }
-        }
+// UTC, без зависимостей от часовых поясов
+fn format_time(time: SystemTime) -> String {
+    let Ok(since_epoch) = time.duration_since(UNIX_EPOCH) else {
+        return "-".to_string();
     };
+    let secs = since_epoch.as_secs();
+    let (days, rem) = (secs / 86400, secs % 86400);
+    // перевод дней от 1970-01-01 в дату григорианского календаря (алгоритм Хиннанта)
+    let z = days as i64 + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + (month <= 2) as i64;
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}:{:02} UTC",
+        year, month, day, rem / 3600, rem % 3600 / 60, rem % 60
+    )
+}
 
-    for entry in read_dir {
-        let entry = match entry {
-            Ok(e) => e,
-            Err(e) => {
-                if ignore_os5 && e.kind() == std::io::ErrorKind::PermissionDenied {
-                    continue;
-                } else {
-                    return Err(e.into());
-                }
-            }
-        };
-
-        let path = entry.path();
-
-        let metadata = match entry.metadata() {
-            Ok(m) => m,
-            Err(e) => {
-                if ignore_os5 && e.kind() == std::io::ErrorKind::PermissionDenied {
-                    continue;
-                } else {
-                    return Err(e.into());
-                }
-            }
-        };
-
-        if metadata.is_symlink() {
-            continue;
-        }
-
-        let name = path.file_name().map_or("".to_string(), |s| s.to_string_lossy().into_owned());
-
-        if metadata.is_dir() {
-            let child = build_tree(&path, ignore_os5)?;
-            total_size += child.total_size();
-            children.push(child);
-        } else if metadata.is_file() {
-            let size = metadata.len();
-            total_size += size;
-            file_total_size += size;
-            file_count += 1;
+// самый старый … самый свежий файл директории, чтобы было видно давно заброшенные
+fn mtime_range_hint(node: &Node) -> String {
+    match (node.oldest_mtime, node.newest_mtime) {
+        (Some(oldest), Some(newest)) if node.is_dir => format!(
+            " | {}: {} … {}",
+            tr("Файлы изменены", "Files modified"),
+            format_date(oldest),
+            format_date(newest)
+        ),
+        _ => String::new(),
+    }
+}
 
-            SCANNED_FILES.fetch_add(1, Ordering::Relaxed);
+fn format_date(time: SystemTime) -> String {
+    let formatted = format_time(time);
+    formatted.split_once(' ').map_or(formatted.clone(), |(date, _)| date.to_string())
+}
 
-            children.push(Node {
-                name,
-                size,
-                path,
-                children: Vec::new(),
-                is_dir: false,
-            });
-        }
+#[cfg(unix)]
+fn platform_details(metadata: &fs::Metadata, units: Units) -> Vec<(String, String)> {
+    use std::os::unix::fs::MetadataExt;
+    let mode = metadata.mode();
+    let mut perms = String::new();
+    for shift in [6, 3, 0] {
+        let bits = (mode >> shift) & 0o7;
+        perms.push(if bits & 4 != 0 { 'r' } else { '-' });
+        perms.push(if bits & 2 != 0 { 'w' } else { '-' });
+        perms.push(if bits & 1 != 0 { 'x' } else { '-' });
     }
+    vec![
+        (tr("Права", "Mode").to_string(), format!("{} ({:04o})", perms, mode & 0o7777)),
+        (tr("Владелец", "Owner").to_string(), format!("uid {}, gid {}", metadata.uid(), metadata.gid())),
+        (tr("Жёстких ссылок", "Hard links").to_string(), metadata.nlink().to_string()),
+        (tr("Занято блоками", "Allocated").to_string(), format_size(metadata.blocks() * 512, units)),
+        ("inode".to_string(), metadata.ino().to_string()),
+    ]
+}
 
-    children.sort_by_key(|c| std::cmp::Reverse(c.total_size()));
+#[cfg(not(unix))]
+fn platform_details(metadata: &fs::Metadata, _units: Units) -> Vec<(String, String)> {
+    let readonly = if metadata.permissions().readonly() { tr("да", "yes") } else { tr("нет", "no") };
+    vec![(tr("Только чтение", "Read-only").to_string(), readonly.to_string())]
+}
 
-    let threshold = if file_count > 0 {
-        let avg_size = file_total_size as f64 / file_count as f64;
-        let count_factor = if file_count > 200 {
-            0.001
-        } else if file_count > 50 {
-            0.005
-        } else {
-            0.01
-        };
-        let size_based = total_size as f64 * count_factor;
-        let avg_based = avg_size * 0.2;
-        size_based.max(avg_based).max(1024.0) as u64
+// truecolor объявляют через COLORTERM (Windows Terminal — через WT_SESSION), 256 цветов видно по имени TERM
+fn detect_color_support() -> ColorSupport {
+    let colorterm = std::env::var("COLORTERM").unwrap_or_default().to_lowercase();
+    if colorterm == "truecolor" || colorterm == "24bit" || std::env::var_os("WT_SESSION").is_some() {
+        return ColorSupport::Truecolor;
+    }
+    if std::env::var("TERM").is_ok_and(|term| term.contains("256")) {
+        ColorSupport::Ansi256
     } else {
-        u64::MAX
-    };
+        ColorSupport::Ansi16
+    }
+}
 
-    let mut other_size = 0u64;
-    let mut filtered = Vec::new();
+// стандартные значения xterm для 16 базовых цветов
+const ANSI_16: [(Color, (u8, u8, u8)); 16] = [
+    (Color::Black, (0, 0, 0)),
+    (Color::Red, (205, 0, 0)),
+    (Color::Green, (0, 205, 0)),
+    (Color::Yellow, (205, 205, 0)),
+    (Color::Blue, (0, 0, 238)),
+    (Color::Magenta, (205, 0, 205)),
+    (Color::Cyan, (0, 205, 205)),
+    (Color::Gray, (229, 229, 229)),
+    (Color::DarkGray, (127, 127, 127)),
+    (Color::LightRed, (255, 0, 0)),
+    (Color::LightGreen, (0, 255, 0)),
+    (Color::LightYellow, (255, 255, 0)),
+    (Color::LightBlue, (92, 92, 255)),
+    (Color::LightMagenta, (255, 0, 255)),
+    (Color::LightCyan, (0, 255, 255)),
+    (Color::White, (255, 255, 255)),
+];
+
+fn color_distance(a: (u8, u8, u8), b: (u8, u8, u8)) -> u32 {
+    let d = |x: u8, y: u8| (x as i32 - y as i32).pow(2) as u32;
+    d(a.0, b.0) + d(a.1, b.1) + d(a.2, b.2)
+}
 
-    for child in children {
-        if !child.is_dir && child.size < threshold {
-            other_size += child.size;
-        } else {
-            filtered.push(child);
-        }
+// ближайший цвет из куба 6×6×6 (16..231) или серой шкалы (232..255)
+fn nearest_256(rgb: (u8, u8, u8)) -> u8 {
+    const LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+    let level = |c: u8| (0..6).min_by_key(|&i| (LEVELS[i] as i32 - c as i32).abs()).unwrap_or(0);
+    let (r, g, b) = (level(rgb.0), level(rgb.1), level(rgb.2));
+    let cube = (LEVELS[r], LEVELS[g], LEVELS[b]);
+    let gray_step = (((rgb.0 as u32 + rgb.1 as u32 + rgb.2 as u32) / 3).saturating_sub(3) / 10).min(23) as u8;
+    let gray = 8 + gray_step * 10;
+    if color_distance(rgb, (gray, gray, gray)) < color_distance(rgb, cube) {
+        232 + gray_step
+    } else {
+        16 + 36 * r as u8 + 6 * g as u8 + b as u8
     }
+}
 
-    if other_size > 0 {
-        filtered.push(Node {
-            name: "Прочее".to_string(),
-            size: other_size,
-            path: root.to_path_buf(),
-            children: Vec::new(),
-            is_dir: false,
-        });
+fn downgrade_color(color: Color, support: ColorSupport) -> Color {
+    if support == ColorSupport::Monochrome {
+        return Color::Reset;
+    }
+    let Color::Rgb(r, g, b) = color else {
+        return color;
+    };
+    match support {
+        ColorSupport::Ansi256 => Color::Indexed(nearest_256((r, g, b))),
+        ColorSupport::Ansi16 => ANSI_16.iter()
+            .min_by_key(|(_, rgb)| color_distance((r, g, b), *rgb))
+            .map_or(color, |(named, _)| *named),
+        ColorSupport::Auto | ColorSupport::Truecolor | ColorSupport::Monochrome => color,
     }
-
-    let name = root.file_name().map_or("".to_string(), |s| s.to_string_lossy().into_owned());
-
-    Ok(Node {
-        name,
-        size: total_size,
-        path: root.to_path_buf(),
-        children: filtered,
-        is_dir: true,
-    })
 }
 
-fn layout_tree<'a>(node: &'a Node, area: Rect, horizontal: bool) -> Vec<(Rect, &'a Node)> {
-    if node.children.is_empty() || area.width < 3 || area.height < 3 {
-        return vec![(area, node)];
+// вызывается после отрисовки кадра: так не нужно помнить о палитре в каждом виджете
+fn downgrade_colors(buf: &mut ratatui::buffer::Buffer) {
+    let Some(&support) = COLOR_SUPPORT.get() else {
+        return;
+    };
+    for cell in &mut buf.content {
+        cell.fg = downgrade_color(cell.fg, support);
+        cell.bg = downgrade_color(cell.bg, support);
     }
+}
 
-    let total = node.size as f64;
-    let children: Vec<&'a Node> = node.children.iter()
-        .filter(|c| c.size > 0)
-        .collect();
+// без цвета плитку заполняет штриховка: чем светлее был бы цвет, тем она плотнее
+fn shade(color: Color) -> &'static str {
+    let Color::Rgb(r, g, b) = color else {
+        return "░";
+    };
+    let luma = 0.299 * r as f64 + 0.587 * g as f64 + 0.114 * b as f64;
+    match luma as u32 {
+        0..60 => " ",
+        60..110 => "░",
+        110..170 => "▒",
+        _ => "▓",
+    }
+}
 
-    if children.is_empty() {
-        return vec![(area, node)];
+// заливка выбранной плитки в режиме без рамок
+fn brighten(color: Color) -> Color {
+    match color {
+        Color::Rgb(r, g, b) => {
+            let lift = |c: u8| c + ((255 - c) as f64 * 0.5) as u8;
+            Color::Rgb(lift(r), lift(g), lift(b))
+        }
+        _ => Color::Yellow,
     }
+}
 
-    let primary_dim = if horizontal { area.width as f64 } else { area.height as f64 };
-    let sizes: Vec<f64> = children.iter()
-        .map(|c| (c.size as f64 / total) * primary_dim)
-        .collect();
+// свежие файлы зелёные, через жёлтый к красным для тех, что не менялись пару лет
+fn age_color(node: &TileInfo, now: SystemTime) -> Color {
+    let Some(mtime) = node.mtime else {
+        return Color::Rgb(110, 110, 110);
+    };
+    let days = now.duration_since(mtime).map_or(0.0, |d| d.as_secs_f64() / 86400.0);
+    let t = ((1.0 + days).ln() / (1.0 + 730.0f64).ln()).clamp(0.0, 1.0);
 
-    let mut integer_sizes: Vec<u16> = sizes.iter().map(|&v| v.floor() as u16).collect();
-    let allocated: u16 = integer_sizes.iter().sum();
-    let remainder = primary_dim as u16 - allocated;
+    let lerp = |a: u8, b: u8, t: f64| (a as f64 + (b as f64 - a as f64) * t) as u8;
+    let (from, to, t) = if t < 0.5 {
+        ((60, 170, 70), (200, 180, 50), t * 2.0)
+    } else {
+        ((200, 180, 50), (190, 50, 50), (t - 0.5) * 2.0)
+    };
+    Color::Rgb(lerp(from.0, to.0, t), lerp(from.1, to.1, t), lerp(from.2, to.2, t))
+}
 
-    let mut fractional: Vec<(usize, f64)> = sizes.iter()
-        .enumerate()
-        .map(|(i, &v)| (i, v.fract()))
-        .collect();
-    fractional.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
+fn dynamic_color(node: &TileInfo, total_size: u64) -> Color {
+    if total_size == 0 {
+        return Color::DarkGray;
+    }
+    
+    let norm = (node.size as f64 / total_size as f64).sqrt();
+    let brightness = (90.0 + 165.0 * norm) as u8;
 
-    for i in 0..remainder as usize {
-        if i < fractional.len() {
-            integer_sizes[fractional[i].0] += 1;
+    if node.is_aggregated && !node.is_bucket {
+        if let Some(color) = THEME.get().and_then(|t| t.other) {
+            return color;
         }
+        let gray = brightness.saturating_sub(30).clamp(60, 180);
+        return Color::Rgb(gray, gray, gray);
     }
 
-    let mut result = Vec::new();
-    let mut current_pos = if horizontal { area.x } else { area.y };
-    let secondary_start = if horizontal { area.y } else { area.x };
-    let secondary_size = if horizontal { area.height } else { area.width };
-
-    for (i, &child) in children.iter().enumerate() {
-        let mut size_primary = integer_sizes[i];
-        if size_primary < 3 && primary_dim >= 3.0 {
-            size_primary = 3;
-        }
-        if size_primary == 0 {
-            continue;
-        }
-
-        let available = if horizontal {
-            area.right().saturating_sub(current_pos)
-        } else {
-            area.bottom().saturating_sub(current_pos)
-        };
-        if size_primary > available {
-            size_primary = available;
-        }
-        if size_primary < 3 {
-            break;
+    if node.is_dir {
+        if let Some(color) = THEME.get().and_then(|t| t.directory) {
+            return color;
         }
-
-        let child_rect = if horizontal {
-            Rect {
-                x: current_pos,
-                y: secondary_start,
-                width: size_primary,
-                height: secondary_size,
-            }
-        } else {
-            Rect {
-                x: secondary_start,
-                y: current_pos,
-                width: secondary_size,
-                height: size_primary,
-            }
-        };
-
-        result.extend(layout_tree(child, child_rect, !horizontal));
-        current_pos += size_primary;
+        let brightness = brightness as u16;
+        let r = brightness / 4;
+        let g = brightness * 2 / 3;
+        let b = brightness * 3 / 4 + 40;
+        return Color::Rgb(r.clamp(30, 120) as u8, g.clamp(100, 220) as u8, b.clamp(120, 255) as u8);
     }
 
-    let remaining = if horizontal {
-        area.right().saturating_sub(current_pos)
+    let base = color_for_extension(node.path.extension().and_then(|s| s.to_str()));
+    if let Color::Rgb(r, g, b) = base {
+        let factor = 0.6 + norm * 0.8;
+        let avg = (r as f64 + g as f64 + b as f64) / 3.0;
+        
+        let r_new = (r as f64 + (r as f64 - avg) * factor).clamp(60.0, 255.0) as u8;
+        let g_new = (g as f64 + (g as f64 - avg) * factor).clamp(60.0, 255.0) as u8;
+        let b_new = (b as f64 + (b as f64 - avg) * factor).clamp(60.0, 255.0) as u8;
+        
+        Color::Rgb(r_new, g_new, b_new)
     } else {
-        area.bottom().saturating_sub(current_pos)
-    };
-    if remaining > 0 && !result.is_empty() {
-        let (last_rect, last_node) = result.pop().unwrap();
-        let new_rect = if horizontal {
-            Rect { width: last_rect.width + remaining, ..last_rect }
-        } else {
-            Rect { height: last_rect.height + remaining, ..last_rect }
-        };
-        result.push((new_rect, last_node));
+        Color::Rgb(brightness, brightness, brightness)
     }
+}
 
-    result
+fn scan_errors_hint(count: usize) -> String {
+    format!("{}: {} (E — {})", tr("Не удалось прочитать путей", "Unreadable paths"), count, tr("список", "list"))
 }
 
 fn clip_rect(rect: Rect, area: Rect) -> Option<Rect> {
@@ -423,15 +1007,78 @@ fn clip_rect(rect: Rect, area: Rect) -> Option<Rect> {
 
 struct App {
     root: Node,
-    layout: Vec<(Rect, Node)>,
+    layout: Vec<(Rect, TileInfo)>,
     layout_dirty: bool,
-    last_area_size: (u16, u16),
+    // раскладки уже посещённых директорий: (директория, область, режим прокрутки)
+    layout_cache: HashMap<(PathBuf, Rect, bool), Vec<(Rect, TileInfo)>>,
+    layout_key: Option<(PathBuf, Rect, bool)>,
+    view_area: Rect,
     selected: Option<PathBuf>,
     current_dir: PathBuf,
-    mouse_pos: (u16, u16),
     offset_x: u16,
     offset_y: u16,
     scroll_mode: bool,
+    disk_usage: bool,
+    // --no-delete: все изменяющие операции выключены
+    read_only: bool,
+    // --units: KiB/MiB вместо kB/MB
+    units: Units,
+    // дерево из --import-ncdu, --import-du или --compare: снято на другой машине, пересканировать нечего
+    imported: bool,
+    filesystem_space: Option<(u64, u64)>,
+    layout_algorithm: LayoutAlgorithm,
+    min_tile: u16,
+    // сколько уровней вложенности рисовать, данные при этом сканируются полностью
+    render_depth: Option<usize>,
+    pending_delete: Option<PathBuf>,
+    status_message: Option<String>,
+    breadcrumb_area: Rect,
+    // миникарта в режиме прокрутки, пустая, когда не показана
+    minimap_area: Rect,
+    search_mode: bool,
+    search_query: String,
+    search_index: usize,
+    selected_tile: Option<usize>,
+    show_help: bool,
+    show_legend: bool,
+    group_by_extension: bool,
+    // '.': цепочки одиночных директорий одной плиткой
+    collapse_chains: bool,
+    view_filter: ViewFilter,
+    // 'v': список вместо карты
+    list_view: bool,
+    show_icons: bool,
+    // 'B': плитки без рамок
+    dense: bool,
+    // --no-color: штриховка и рамки вместо цвета
+    monochrome: bool,
+    warn_size: Option<u64>,
+    animate: bool,
+    zoom: Option<ZoomAnimation>,
+    // --follow-root-only: путь-симлинк, под которым показываем корень
+    root_alias: Option<PathBuf>,
+    // 'p': в подписях путь относительно текущей директории вместо имени
+    show_paths: bool,
+    legend: Option<(PathBuf, ExtensionTotals)>,
+    sort_mode: SortMode,
+    scan_options: ScanOptions,
+    color_mode: ColorMode,
+    compare_base: Option<HashMap<PathBuf, u64>>,
+    // размеры директорий после каждого пересканирования, не больше SIZE_HISTORY_LEN
+    size_history: HashMap<PathBuf, VecDeque<u64>>,
+    scan_errors: Vec<(PathBuf, std::io::Error)>,
+    // --respect-gitignore: сколько записей пропущено в каждой директории
+    ignored_entries: HashMap<PathBuf, u64>,
+    // --count-hardlinks-once: какая копия каждой жёсткой ссылки уже посчитана, чтобы 'r' и --watch не считали её снова
+    hardlinks: HardlinkOwners,
+    // 'E': список ошибок чтения и его прокрутка
+    show_errors: bool,
+    // 'g': гистограмма размеров файлов
+    show_histogram: bool,
+    errors_scroll: usize,
+    // 'i': подробности о выбранном узле, собираются в момент открытия
+    details: Option<Vec<(String, String)>>,
+    details_scroll: u16,
 }
 
 impl App {
@@ -441,13 +1088,58 @@ impl App {
             root,
             layout: Vec::new(),
             layout_dirty: true,
-            last_area_size: (0, 0),
+            layout_cache: HashMap::new(),
+            layout_key: None,
+            view_area: Rect::default(),
             selected: None,
             current_dir,
-            mouse_pos: (0, 0),
             offset_x: 0,
             offset_y: 0,
             scroll_mode: false,
+            disk_usage: false,
+            read_only: false,
+            units: Units::Decimal,
+            imported: false,
+            filesystem_space: None,
+            layout_algorithm: LayoutAlgorithm::Slice,
+            min_tile: 3,
+            render_depth: None,
+            pending_delete: None,
+            status_message: None,
+            breadcrumb_area: Rect::default(),
+            minimap_area: Rect::default(),
+            search_mode: false,
+            search_query: String::new(),
+            search_index: 0,
+            selected_tile: None,
+            show_help: false,
+            show_legend: false,
+            group_by_extension: false,
+            collapse_chains: false,
+            view_filter: ViewFilter::All,
+            list_view: false,
+            show_icons: true,
+            dense: false,
+            monochrome: false,
+            warn_size: None,
+            animate: true,
+            zoom: None,
+            root_alias: None,
+            show_paths: false,
+            legend: None,
+            sort_mode: SortMode::Size,
+            scan_options: ScanOptions::default(),
+            color_mode: ColorMode::Extension,
+            compare_base: None,
+            size_history: HashMap::new(),
+            scan_errors: Vec::new(),
+            ignored_entries: HashMap::new(),
+            hardlinks: HardlinkOwners::new(),
+            show_errors: false,
+            show_histogram: false,
+            errors_scroll: 0,
+            details: None,
+            details_scroll: 0,
         }
     }
 
@@ -455,23 +1147,503 @@ impl App {
         if self.root.path == path {
             return Some(&self.root);
         }
+        // спускаемся только в тех детей, под которыми путь может лежать: узел находится за глубину пути,
+        // и "Прочее" не перехватывает поиск своей директории
         fn recurse<'b>(node: &'b Node, path: &Path) -> Option<&'b Node> {
             if node.path == path {
                 return Some(node);
             }
+            node.children.iter()
+                .filter(|child| may_contain(child, path))
+                .find_map(|child| recurse(child, path))
+        }
+        recurse(&self.root, path)
+    }
+
+    fn navigate_to(&mut self, path: PathBuf) {
+        self.current_dir = path;
+        self.offset_x = 0;
+        self.offset_y = 0;
+        self.layout_dirty = true;
+    }
+
+    // плитка директории растёт до размеров холста, под ней уже нарисовано её содержимое
+    fn zoom_into(&mut self, path: PathBuf) {
+        if self.animate
+            && !self.list_view
+            && let Some(node) = self.find_node(&path)
+        {
+            // директория нарисована либо одной плиткой, либо плитками своего содержимого;
+            // файлы "Прочее" лежат не под его путём, их узнаём по списку детей
+            let from = self.layout.iter()
+                .filter(|(_, n)| n.path.starts_with(&path) || node.is_aggregated && node.children.iter().any(|c| c.path == n.path))
+                .map(|(rect, _)| *rect)
+                .reduce(|a, b| a.union(b));
+            let total_size = self.find_node(&self.current_dir).map_or(0, |n| n.size);
+            self.zoom = from.map(|mut from| {
+                if self.scroll_mode {
+                    from.x = from.x.saturating_sub(self.offset_x);
+                    from.y = from.y.saturating_sub(self.offset_y);
+                }
+                ZoomAnimation {
+                    from,
+                    color: tile_color(self, &TileInfo::from(node), total_size, SystemTime::now(), 0),
+                    name: node.name.clone(),
+                    start: Instant::now(),
+                }
+            });
+        }
+        self.navigate_to(path);
+    }
+
+    // путь так, как его вводил пользователь: с симлинком корня вместо цели
+    fn shown_path<'a>(&self, path: &'a Path) -> Cow<'a, Path> {
+        if is_other_path(path) {
+            return Cow::Owned(self.shown_path(real_dir(path)).join(OTHER_SHOWN));
+        }
+        if is_synthetic_path(path) {
+            let name = path.file_name().map_or(String::new(), |n| n.to_string_lossy().trim_start_matches('\0').to_string());
+            return Cow::Owned(self.shown_path(real_dir(path)).join(name));
+        }
+        match (&self.root_alias, path.strip_prefix(&self.root.path)) {
+            (Some(alias), Ok(rel)) if rel.as_os_str().is_empty() => Cow::Owned(alias.clone()),
+            (Some(alias), Ok(rel)) => Cow::Owned(alias.join(rel)),
+            _ => display_path(path),
+        }
+    }
+
+    fn breadcrumbs(&self) -> Vec<(String, PathBuf)> {
+        let root_label = if self.root.name.is_empty() {
+            self.shown_path(&self.root.path).display().to_string()
+        } else {
+            self.root.name.clone()
+        };
+        let mut crumbs = vec![(root_label, self.root.path.clone())];
+        if let Ok(rel) = self.current_dir.strip_prefix(&self.root.path) {
+            let mut path = self.root.path.clone();
+            for part in rel.components() {
+                path.push(part);
+                let label = if is_other_path(&path) {
+                    tr("Прочее", "Other").to_string()
+                } else {
+                    part.as_os_str().to_string_lossy().into_owned()
+                };
+                crumbs.push((label, path.clone()));
+            }
+        }
+        crumbs
+    }
+
+    fn breadcrumb_at(&self, x: u16, y: u16) -> Option<PathBuf> {
+        let area = self.breadcrumb_area;
+        if y != area.y || x < area.x {
+            return None;
+        }
+        let sep_width = Span::raw(BREADCRUMB_SEPARATOR).width() as u16;
+        let mut pos = area.x;
+        for (i, (label, path)) in self.breadcrumbs().into_iter().enumerate() {
+            if i > 0 {
+                pos += sep_width;
+            }
+            let width = Span::raw(label).width() as u16;
+            if x >= pos && x < pos + width {
+                return Some(path);
+            }
+            pos += width;
+        }
+        None
+    }
+
+    // сколько клеток холста приходится на клетку миникарты по горизонтали и вертикали
+    fn minimap_scale(&self, inner: Rect) -> (f64, f64) {
+        let right = self.layout.iter().map(|(r, _)| r.right()).max().unwrap_or(0);
+        let bottom = self.layout.iter().map(|(r, _)| r.bottom()).max().unwrap_or(0);
+        (
+            right.saturating_sub(self.view_area.x) as f64 / inner.width.max(1) as f64,
+            bottom.saturating_sub(self.view_area.y) as f64 / inner.height.max(1) as f64,
+        )
+    }
+
+    // клик по миникарте центрирует видимую область на этой точке холста
+    fn jump_to_minimap(&mut self, x: u16, y: u16) -> bool {
+        let inner = self.minimap_area.inner(Margin::new(1, 1));
+        if !self.scroll_mode || x < inner.x || x >= inner.right() || y < inner.y || y >= inner.bottom() {
+            return false;
+        }
+        let (scale_x, scale_y) = self.minimap_scale(inner);
+        let canvas_x = ((x - inner.x) as f64 + 0.5) * scale_x;
+        let canvas_y = ((y - inner.y) as f64 + 0.5) * scale_y;
+        self.offset_x = (canvas_x as u16).saturating_sub(self.view_area.width / 2);
+        self.offset_y = (canvas_y as u16).saturating_sub(self.view_area.height / 2);
+        self.clamp_offsets();
+        true
+    }
+
+    fn search_matches(&self) -> Vec<PathBuf> {
+        fn recurse(node: &Node, query: &str, out: &mut Vec<PathBuf>) {
             for child in &node.children {
-                if let Some(found) = recurse(child, path) {
-                    return Some(found);
+                if name_matches(&child.name, query) {
+                    out.push(child.path.clone());
                 }
+                recurse(child, query, out);
             }
-            None
         }
-        recurse(&self.root, path)
+        let mut matches = Vec::new();
+        if !self.search_query.is_empty() {
+            let current_node = self.find_node(&self.current_dir).unwrap_or(&self.root);
+            recurse(current_node, &self.search_query.to_lowercase(), &mut matches);
+        }
+        matches
+    }
+
+    fn select_next_match(&mut self) {
+        let matches = self.search_matches();
+        if matches.is_empty() {
+            self.status_message = Some(format!("{}: {}", tr("Ничего не найдено", "No matches"), self.search_query));
+            return;
+        }
+        let idx = self.search_index % matches.len();
+        self.status_message = Some(format!("{} {}/{}", tr("Совпадение", "Match"), idx + 1, matches.len()));
+        self.selected_tile = self.layout.iter().position(|(_, n)| n.path == matches[idx]);
+        self.selected = Some(matches[idx].clone());
+        self.search_index = idx + 1;
+    }
+
+    // размер в старом снимке в режиме сравнения; чего не было, то считаем нулём
+    fn size_before(&self, node: &TileInfo) -> Option<u64> {
+        let base = self.compare_base.as_ref()?;
+        Some(base.get(&node.path).copied().unwrap_or(0))
+    }
+
+    fn selected_info(&self) -> Option<TileInfo> {
+        let selected = self.selected.as_ref()?;
+        self.selected_tile
+            .and_then(|i| self.layout.get(i))
+            .map(|(_, n)| n.clone())
+            .filter(|n| &n.path == selected)
+            .or_else(|| self.find_node(selected).map(TileInfo::from))
+    }
+
+    fn open_details(&mut self) {
+        let Some(node) = self.selected_info() else {
+            return;
+        };
+        let mut rows = vec![
+            (tr("Путь", "Path").to_string(), self.shown_path(&node.path).display().to_string()),
+            (tr("Размер", "Size").to_string(), self.size_label(&node)),
+        ];
+        if node.is_dir {
+            let children = self.find_node(&node.path).map_or(0, |n| n.children.len());
+            rows.push((tr("Внутри", "Children").to_string(), format_count(children as u64)));
+            rows.push((tr("Файлов", "Files").to_string(), format_count(node.file_count)));
+            rows.push((tr("Папок", "Dirs").to_string(), format_count(node.dir_count)));
+        }
+        if node.incomplete {
+            rows.push((tr("Внимание", "Warning").to_string(), tr("прочитано не всё", "not fully read").to_string()));
+        }
+        if node.is_aggregated || node.is_bucket {
+            // у сводных узлов своего файла нет, метаданные были бы от родительской директории
+            let kind = if node.is_bucket { tr("группа по расширению", "extension group") } else { tr("сводный узел мелких файлов", "small files summary") };
+            rows.push((tr("Тип", "Type").to_string(), kind.to_string()));
+            self.details = Some(rows);
+            self.details_scroll = 0;
+            return;
+        }
+        match fs::symlink_metadata(&node.path) {
+            Ok(metadata) => {
+                let kind = if metadata.is_symlink() {
+                    tr("символическая ссылка", "symlink")
+                } else if metadata.is_dir() {
+                    tr("директория", "directory")
+                } else {
+                    tr("файл", "file")
+                };
+                rows.push((tr("Тип", "Type").to_string(), kind.to_string()));
+                if metadata.is_symlink() && let Ok(target) = fs::read_link(&node.path) {
+                    rows.push((tr("Цель", "Target").to_string(), target.display().to_string()));
+                }
+                rows.push((tr("Размер сейчас", "Current size").to_string(), format_size(metadata.len(), self.units)));
+                if let Ok(modified) = metadata.modified() {
+                    rows.push((tr("Изменён", "Modified").to_string(), format_time(modified)));
+                }
+                if let Ok(accessed) = metadata.accessed() {
+                    rows.push((tr("Открыт", "Accessed").to_string(), format_time(accessed)));
+                }
+                rows.extend(platform_details(&metadata, self.units));
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                rows.push((tr("Внимание", "Warning").to_string(), tr("удалён после сканирования", "deleted since the scan").to_string()));
+            }
+            Err(e) => rows.push((tr("Ошибка", "Error").to_string(), e.to_string())),
+        }
+        self.details = Some(rows);
+        self.details_scroll = 0;
+    }
+
+    fn size_label(&self, node: &TileInfo) -> String {
+        match self.size_before(node) {
+            Some(before) => format!(
+                "{} → {} ({})",
+                format_size(before, self.units),
+                format_size(node.size, self.units),
+                format_delta(before, node.size, self.units)
+            ),
+            None => format_size(node.size, self.units),
+        }
+    }
+
+    // '+'/'-': без ограничения глубина равна высоте поддерева текущей директории
+    fn change_render_depth(&mut self, delta: isize) {
+        fn height(node: &Node) -> usize {
+            node.children.iter().map(|c| height(c) + 1).max().unwrap_or(0)
+        }
+        let max = height(self.find_node(&self.current_dir).unwrap_or(&self.root)).max(1);
+        let depth = self.render_depth.unwrap_or(max).min(max).saturating_add_signed(delta).max(1);
+        self.render_depth = if depth >= max { None } else { Some(depth) };
+        self.invalidate_layout();
+    }
+
+    // ошибки и пропуски по .gitignore пересканированного поддерева заменяют прежние, остальные остаются
+    fn take_scan_results(&mut self, path: &Path, ctx: &ScanContext) {
+        self.scan_errors.retain(|(p, _)| !p.starts_with(path));
+        self.scan_errors.append(&mut ctx.errors.lock().unwrap());
+        self.errors_scroll = 0;
+        self.ignored_entries.retain(|dir, _| !dir.starts_with(path));
+        self.ignored_entries.extend(ctx.ignored_entries.lock().unwrap().drain());
+        self.hardlinks = ctx.take_hardlinks();
+    }
+
+    fn record_size(&mut self, path: &Path, size: u64) {
+        let history = self.size_history.entry(path.to_path_buf()).or_default();
+        if history.len() == SIZE_HISTORY_LEN {
+            history.pop_front();
+        }
+        history.push_back(size);
+    }
+
+    // строки списка 'v': дети текущей директории от большего к меньшему, при любой сортировке 's'
+    fn list_entries(&self) -> Vec<&Node> {
+        let current = self.find_node(&self.current_dir).unwrap_or(&self.root);
+        let mut entries: Vec<&Node> = current.children.iter().collect();
+        entries.sort_by(|a, b| b.size.cmp(&a.size).then_with(|| a.name.cmp(&b.name)));
+        entries
+    }
+
+    fn move_list_selection(&mut self, delta: isize) {
+        let entries = self.list_entries();
+        if entries.is_empty() {
+            return;
+        }
+        let index = match self.selected.as_ref().and_then(|p| entries.iter().position(|n| &n.path == p)) {
+            Some(i) => i.saturating_add_signed(delta).min(entries.len() - 1),
+            None => 0,
+        };
+        self.selected = Some(entries[index].path.clone());
+    }
+
+    // 1-9 в списке: директорию открываем, файл выбираем
+    fn open_ranked(&mut self, index: usize) {
+        let Some(node) = self.list_entries().get(index).copied() else {
+            return;
+        };
+        let path = node.path.clone();
+        if node.is_dir && !node.children.is_empty() {
+            self.navigate_to(path);
+            self.selected = None;
+        } else {
+            self.selected = Some(path);
+        }
+    }
+
+    fn toggle_dirs_first(&mut self) {
+        self.scan_options.dirs_first = !self.scan_options.dirs_first;
+        sort_tree(&mut self.root, self.sort_mode, self.scan_options.dirs_first);
+        self.invalidate_layout();
+    }
+
+    // 'u': размер по длине файлов или по занятым блокам; в узлах есть оба, пересканировать не нужно
+    fn toggle_disk_usage(&mut self) {
+        self.disk_usage = !self.disk_usage;
+        self.scan_options.disk_usage = self.disk_usage;
+        self.root.use_disk_size(self.disk_usage);
+        // порог "Прочее" зависит от размеров, состав сводных узлов пересчитываем под новое измерение
+        let depth = self.root.path.strip_prefix(&self.scan_options.scan_root).map_or(0, |rel| rel.components().count());
+        regroup_other(&mut self.root, &self.scan_options, depth);
+        sort_tree(&mut self.root, self.sort_mode, self.scan_options.dirs_first);
+        self.invalidate_layout();
+    }
+
+    fn cycle_sort_mode(&mut self) {
+        self.sort_mode = self.sort_mode.next();
+        sort_tree(&mut self.root, self.sort_mode, self.scan_options.dirs_first);
+        self.invalidate_layout();
+    }
+
+    // дерево изменилось: закэшированные раскладки больше не годятся
+    fn invalidate_layout(&mut self) {
+        self.layout_cache.clear();
+        self.layout_key = None;
+        self.legend = None;
+        self.layout_dirty = true;
+    }
+
+    fn replace_node(&mut self, mut new_node: Node) -> bool {
+        let Some(old) = self.find_node(&new_node.path) else {
+            return false;
+        };
+        let (old_size, old_apparent, old_disk) = (old.size, old.apparent_size, old.disk_size);
+        let (old_files, old_dirs) = old.entry_counts();
+        let (new_files, new_dirs) = new_node.entry_counts();
+        new_node.mtime = old.mtime;
+
+        let path = new_node.path.clone();
+        let mut node = &mut self.root;
+        loop {
+            if node.path == path {
+                *node = new_node;
+                refresh_mtime_range(&mut self.root, &path);
+                return true;
+            }
+            node.size = node.size.saturating_sub(old_size) + new_node.size;
+            node.apparent_size = node.apparent_size.saturating_sub(old_apparent) + new_node.apparent_size;
+            node.disk_size = node.disk_size.saturating_sub(old_disk) + new_node.disk_size;
+            node.file_count = node.file_count.saturating_sub(old_files) + new_files;
+            node.dir_count = node.dir_count.saturating_sub(old_dirs) + new_dirs;
+            match node.children.iter_mut().find(|c| c.is_dir && path.starts_with(&c.path)) {
+                Some(child) => node = child,
+                None => return false,
+            }
+        }
+    }
+
+    // забирает детей директории, её размеры и счётчики остаются прежними до replace_node
+    fn take_children(&mut self, dir: &Path) -> Option<Vec<Node>> {
+        let mut node = &mut self.root;
+        while node.path != dir {
+            node = node.children.iter_mut().find(|c| c.is_dir && dir.starts_with(&c.path))?;
+        }
+        Some(std::mem::take(&mut node.children))
+    }
+
+    // --watch: запись path появилась, изменилась или пропала (entry == None). Её директория собирается заново
+    // из своих детей, чтобы пересчитать "Прочее", предков поправляет replace_node
+    fn update_entry(&mut self, path: &Path, entry: Option<Node>) {
+        let Some(dir) = path.parent() else {
+            return;
+        };
+        let Some(parent) = self.find_node(dir).filter(|n| n.is_dir && !n.is_aggregated) else {
+            return;
+        };
+        // неполной директория остаётся, только если неполна она сама, а не кто-то из детей
+        let skipped = parent.incomplete && !parent.children.iter().any(|c| c.incomplete);
+        // у корня может быть подпись --root-label вместо имени
+        let name = parent.name.clone();
+        let Some(children) = self.take_children(dir) else {
+            return;
+        };
+        let mut children: Vec<Node> = children.into_iter()
+            .flat_map(|c| if c.is_aggregated { c.children } else { vec![c] })
+            .filter(|c| c.path != path)
+            .collect();
+        if let Some(mut entry) = entry {
+            sort_tree(&mut entry, self.sort_mode, self.scan_options.dirs_first);
+            children.push(entry);
+        }
+        let depth = dir.strip_prefix(&self.scan_options.scan_root).map_or(0, |rel| rel.components().count());
+        let mut node = assemble_dir(dir, children, skipped, &self.scan_options, depth);
+        node.name = name;
+        sort_nodes(&mut node.children, self.sort_mode, self.scan_options.dirs_first);
+        for other in node.children.iter_mut().filter(|c| c.is_aggregated) {
+            sort_nodes(&mut other.children, self.sort_mode, self.scan_options.dirs_first);
+        }
+        self.replace_node(node);
+    }
+
+    fn remove_node(&mut self, path: &Path) -> Option<u64> {
+        // возвращает удалённое поддерево, предки вычитают его размеры и счётчики
+        fn recurse(node: &mut Node, path: &Path) -> Option<Node> {
+            let removed = if let Some(idx) = node.children.iter().position(|c| c.path == path) {
+                node.children.remove(idx)
+            } else {
+                node.children.iter_mut()
+                    .filter(|c| c.is_dir && may_contain(c, path))
+                    .find_map(|c| recurse(c, path))?
+            };
+            let (files, dirs) = removed.entry_counts();
+            node.size = node.size.saturating_sub(removed.size);
+            node.apparent_size = node.apparent_size.saturating_sub(removed.apparent_size);
+            node.disk_size = node.disk_size.saturating_sub(removed.disk_size);
+            node.file_count = node.file_count.saturating_sub(files);
+            node.dir_count = node.dir_count.saturating_sub(dirs);
+            (node.newest_mtime, node.oldest_mtime) = mtime_range(&node.children);
+            Some(removed)
+        }
+        recurse(&mut self.root, path).map(|removed| removed.size)
+    }
+
+    fn request_delete(&mut self) {
+        if self.read_only {
+            self.status_message = Some(tr("Режим только для чтения", "Read-only mode").to_string());
+            return;
+        }
+        let Some(selected) = &self.selected else {
+            return;
+        };
+        // "Прочее" и плитки представлений не существуют на диске, а корень удалять нельзя
+        let node = self.find_node(selected);
+        if is_synthetic_path(selected) || node.is_some_and(|n| n.is_aggregated) || *selected == self.root.path {
+            self.status_message = Some(tr("Этот элемент нельзя удалить", "This item cannot be deleted").to_string());
+            return;
+        }
+        let Some(node) = node else {
+            return;
+        };
+        self.pending_delete = Some(node.path.clone());
+    }
+
+    // пути импортированного дерева на этой машине ведут к чему-то другому или никуда
+    fn can_rescan(&mut self) -> bool {
+        if self.imported {
+            self.status_message = Some(tr("Импортированное дерево нельзя пересканировать", "An imported tree cannot be rescanned").to_string());
+            return false;
+        }
+        true
+    }
+
+    fn confirm_delete(&mut self) {
+        let Some(path) = self.pending_delete.take() else {
+            return;
+        };
+        if self.read_only {
+            return;
+        }
+        let is_dir = self.find_node(&path).is_some_and(|n| n.is_dir);
+        let result = if is_dir {
+            fs::remove_dir_all(&path)
+        } else {
+            fs::remove_file(&path)
+        };
+
+        match result {
+            Ok(()) => {
+                self.remove_node(&path);
+                self.selected = None;
+                self.invalidate_layout();
+                self.status_message = Some(format!("{}: {}", tr("Удалено", "Deleted"), path.display()));
+            }
+            Err(e) => {
+                self.status_message = Some(format!("{} {}: {}", tr("Не удалось удалить", "Failed to delete"), path.display(), e));
+            }
+        }
+    }
+
+    fn get_tile_at(&self, x: u16, y: u16) -> Option<&TileInfo> {
+        self.tile_at(x, y).map(|i| &self.layout[i].1)
     }
 
-    fn get_node_at(&self, x: u16, y: u16) -> Option<&Node> {
+    fn tile_at(&self, x: u16, y: u16) -> Option<usize> {
         self.layout.iter()
-            .find(|(rect, _)| {
+            .position(|(rect, _)| {
                 let rx = rect.x as i32 - self.offset_x as i32;
                 let ry = rect.y as i32 - self.offset_y as i32;
                 let rw = rect.width as i32;
@@ -479,36 +1651,115 @@ impl App {
                 (x as i32) >= rx && (x as i32) < rx + rw && 
                 (y as i32) >= ry && (y as i32) < ry + rh
             })
-            .map(|(_, node)| node)
+    }
+
+    fn select_tile(&mut self, idx: Option<usize>) {
+        self.selected_tile = idx;
+        self.selected = idx.map(|i| self.layout[i].1.path.clone());
+    }
+
+    // выбирает ближайшую плитку в направлении (dx, dy) от текущей
+    fn move_selection(&mut self, dx: i32, dy: i32) {
+        let Some(current) = self.selected_tile.filter(|&i| i < self.layout.len()) else {
+            if !self.layout.is_empty() {
+                self.select_tile(Some(0));
+            }
+            return;
+        };
+        let cur = self.layout[current].0;
+        let (cx, cy) = (cur.x as i32 * 2 + cur.width as i32, cur.y as i32 * 2 + cur.height as i32);
+
+        let best = self.layout.iter()
+            .enumerate()
+            .filter(|&(i, _)| i != current)
+            .filter_map(|(i, (rect, _))| {
+                let gap = match (dx, dy) {
+                    (1, _) => rect.x as i32 - cur.right() as i32,
+                    (-1, _) => cur.x as i32 - rect.right() as i32,
+                    (_, 1) => rect.y as i32 - cur.bottom() as i32,
+                    _ => cur.y as i32 - rect.bottom() as i32,
+                };
+                if gap < 0 {
+                    return None;
+                }
+                let (rx, ry) = (rect.x as i32 * 2 + rect.width as i32, rect.y as i32 * 2 + rect.height as i32);
+                let offset = if dx != 0 { (ry - cy).abs() } else { (rx - cx).abs() };
+                Some((gap * 4 + offset, i))
+            })
+            .min();
+
+        if let Some((_, idx)) = best {
+            self.select_tile(Some(idx));
+        }
     }
 
     fn ensure_layout(&mut self, area: Rect) {
-        let area_size = (area.width, area.height);
         let new_scroll_mode = area.width < 40 || area.height < 20;
         
         if self.layout_dirty 
-            || self.last_area_size != area_size 
+            || self.view_area != area 
             || self.scroll_mode != new_scroll_mode 
         {
-            self.recalculate_layout(area);
-            self.last_area_size = area_size;
             self.scroll_mode = new_scroll_mode;
+            self.recalculate_layout(area);
+            self.view_area = area;
             self.layout_dirty = false;
+            self.selected_tile = self.selected.as_ref()
+                .and_then(|p| self.layout.iter().position(|(_, n)| &n.path == p));
         }
     }
 
+    // смещения, при которых правый/нижний край раскладки совпадает с краем экрана
+    fn max_offsets(&self) -> (u16, u16) {
+        let right = self.layout.iter().map(|(r, _)| r.right()).max().unwrap_or(0);
+        let bottom = self.layout.iter().map(|(r, _)| r.bottom()).max().unwrap_or(0);
+        (
+            right.saturating_sub(self.view_area.right()),
+            bottom.saturating_sub(self.view_area.bottom()),
+        )
+    }
+
+    fn clamp_offsets(&mut self) {
+        let (max_x, max_y) = self.max_offsets();
+        self.offset_x = self.offset_x.min(max_x);
+        self.offset_y = self.offset_y.min(max_y);
+    }
+
+    fn center_on_selected(&mut self) {
+        let Some(rect) = self.selected_tile.and_then(|i| self.layout.get(i)).map(|(r, _)| *r) else {
+            return;
+        };
+        let area = self.view_area;
+        self.offset_x = (rect.x + rect.width / 2).saturating_sub(area.x + area.width / 2);
+        self.offset_y = (rect.y + rect.height / 2).saturating_sub(area.y + area.height / 2);
+        self.clamp_offsets();
+    }
+
     fn recalculate_layout(&mut self, area: Rect) {
+        let key = (self.current_dir.clone(), area, self.scroll_mode);
+        let previous = std::mem::take(&mut self.layout);
+        if let Some(previous_key) = self.layout_key.replace(key.clone()) {
+            if self.layout_cache.len() >= 64 {
+                self.layout_cache.clear();
+            }
+            self.layout_cache.insert(previous_key, previous);
+        }
+        if let Some(cached) = self.layout_cache.remove(&key) {
+            self.layout = cached;
+            return;
+        }
+
         let current_node = self.find_node(&self.current_dir).unwrap_or(&self.root);
-        let total_size = current_node.size;
 
         let layout_area = if self.scroll_mode {
-            let node_count = current_node.children.len() as u16;
-            let base_size = 200u16;
-            let dynamic_size = (base_size + node_count * 5).min(5000);
+            // в usize: десятки тысяч детей переполнили бы u16 ещё до min
+            let node_count = current_node.children.len();
+            let base_size = 200usize;
+            let dynamic_size = base_size.saturating_add(node_count.saturating_mul(5)).min(5000) as u16;
             
             Rect {
-                x: 0,
-                y: 0,
+                x: area.x,
+                y: area.y,
                 width: dynamic_size,
                 height: dynamic_size,
             }
@@ -516,176 +1767,1096 @@ impl App {
             area
         };
 
-        self.layout = layout_tree(current_node, layout_area, true)
+        let filtered = (self.view_filter != ViewFilter::All).then(|| filter_view(current_node, self.view_filter));
+        let current_node = filtered.as_ref().unwrap_or(current_node);
+        let collapsed = self.collapse_chains.then(|| collapse_chains(current_node));
+        let current_node = collapsed.as_ref().unwrap_or(current_node);
+        let grouped = self.group_by_extension.then(|| group_by_extension(current_node, self.sort_mode, self.scan_options.dirs_first));
+        let layout_node = grouped.as_ref().unwrap_or(current_node);
+        let tiles = match self.layout_algorithm {
+            LayoutAlgorithm::Slice => layout_tree(layout_node, layout_area, true, false, self.min_tile, self.render_depth),
+            LayoutAlgorithm::SliceLongest => layout_tree(layout_node, layout_area, true, true, self.min_tile, self.render_depth),
+            LayoutAlgorithm::Squarified => layout_squarified(layout_node, layout_area, self.min_tile, self.render_depth),
+        };
+        let layout = tiles
             .into_iter()
-            .map(|(r, n)| (r, n.clone()))
+            .map(|(r, n)| {
+                let mut tile = TileInfo::from(n);
+                if grouped.is_some() {
+                    // в сгруппированном виде директории плоские, о детях спрашиваем настоящее дерево
+                    tile.is_bucket = n.is_aggregated && !is_other_path(&n.path);
+                }
+                if grouped.is_some() || filtered.is_some() {
+                    tile.has_children = n.is_dir && self.find_node(&n.path).is_some_and(|real| !real.children.is_empty());
+                }
+                (r, tile)
+            })
             .collect();
+        self.layout = layout;
     }
 }
 
-fn main() -> Result<()> {
-    let args = Args::parse();
-    let path = args.path.canonicalize()?;
-//
-use std::time::{Duration, Instant};
-use std::sync::Arc;
+// где остановились в прошлый раз: восстанавливается, только если снова открыт тот же корень
+#[derive(Serialize, Deserialize)]
+struct SavedState {
+    root: PathBuf,
+    current_dir: PathBuf,
+    offset_x: u16,
+    offset_y: u16,
+}
 
-println!("Сканирую директорию...");
+fn state_file() -> Option<PathBuf> {
+    let dir = std::env::var_os("XDG_STATE_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("LOCALAPPDATA").map(PathBuf::from))
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".local").join("state")))?;
+    Some(dir.join("volume-inspector").join("state.json"))
+}
 
-let done = Arc::new(AtomicBool::new(false));
-let done_flag = done.clone();
+fn save_state(app: &App) -> Result<()> {
+    let Some(file) = state_file() else {
+        return Ok(());
+    };
+    if let Some(dir) = file.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let state = SavedState {
+        root: app.root.path.clone(),
+        current_dir: app.current_dir.clone(),
+        offset_x: app.offset_x,
+        offset_y: app.offset_y,
+    };
+    serde_json::to_writer(BufWriter::new(fs::File::create(file)?), &state)?;
+    Ok(())
+}
 
-let start_time = Instant::now();
+fn restore_state(app: &mut App) {
+    let Some(state) = state_file()
+        .and_then(|file| fs::File::open(file).ok())
+        .and_then(|f| serde_json::from_reader::<_, SavedState>(BufReader::new(f)).ok())
+    else {
+        return;
+    };
+    // директории могло уже не оказаться в новом скане — тогда остаёмся в корне
+    if state.root != app.root.path || !app.find_node(&state.current_dir).is_some_and(|n| n.is_dir) {
+        return;
+    }
+    app.navigate_to(state.current_dir);
+    app.offset_x = state.offset_x;
+    app.offset_y = state.offset_y;
+}
 
-// Поток прогресса
-let progress_thread = std::thread::spawn(move || {
-    let mut last_count = 0u64;
-    let mut last_time = Instant::now();
+fn print_tree(out: &mut impl Write, node: &Node, level: usize, max_level: Option<usize>, warn_size: Option<u64>, units: Units) -> Result<()> {
+    let name = if level == 0 {
+        node.path.display().to_string()
+    } else if node.is_dir {
+        format!("{}/", node.name)
+    } else {
+        node.name.clone()
+    };
+    let warning = if warn_size.is_some_and(|limit| node.size > limit) { "  !" } else { "" };
+    writeln!(out, "{}{}  {}{}", "  ".repeat(level), name, format_size(node.size, units), warning)?;
 
-    while !done_flag.load(Ordering::Relaxed) {
-        let count = SCANNED_FILES.load(Ordering::Relaxed);
+    if max_level.is_some_and(|max| level >= max) {
+        return Ok(());
+    }
+    for child in &node.children {
+        print_tree(out, child, level + 1, max_level, warn_size, units)?;
+    }
+    Ok(())
+}
 
-        let now = Instant::now();
-        let dt = now.duration_since(last_time).as_secs_f64().max(0.001);
-        let speed = (count - last_count) as f64 / dt;
+fn print_profile(elapsed: Duration, ctx: &ScanContext) {
+    const SLOWEST: usize = 10;
+    let mut timings = std::mem::take(&mut *ctx.dir_timings.lock().unwrap());
+    timings.sort_by_key(|(_, time)| std::cmp::Reverse(*time));
+    eprintln!("⏱  {}: {:.3} {}", tr("Профиль сканирования", "Scan profile"), elapsed.as_secs_f64(), tr("сек", "s"));
+    eprintln!(
+        "   read_dir: {} | metadata: {}",
+        format_count(ctx.read_dir_calls.load(Ordering::Relaxed)),
+        format_count(ctx.metadata_calls.load(Ordering::Relaxed))
+    );
+    if timings.is_empty() {
+        return;
+    }
+    eprintln!("   {}:", tr("Дольше всего читался список", "Slowest directory listings"));
+    for (path, time) in timings.iter().take(SLOWEST) {
+        eprintln!("   {:>10.3} {}  {}", time.as_secs_f64() * 1000.0, tr("мс", "ms"), path.display());
+    }
+}
 
-        last_time = now;
-        last_count = count;
+fn scan_with_progress(path: &Path, opts: &ScanOptions, ctx: &Arc<ScanContext>, expected_files: Option<u64>, quiet: bool, units: Units) -> Result<Node> {
+    use indicatif::{ProgressBar, ProgressStyle};
 
-        let path = CURRENT_PATH.lock().unwrap().clone();
+    // --quiet: поток прогресса не нужен вовсе, итоги тоже не печатаем
+    if quiet {
+        return scan(path, &ScanOptions { progress: None, ..opts.clone() }, ctx);
+    }
 
-        print!(
-            "\r📁 {} | 📄 файлов: {} | ⚡ {:.0} файлов/сек        ",
-            path, count, speed
-        );
+    println!("{}", tr("Сканирую директорию...", "Scanning directory..."));
 
-        use std::io::Write;
-        std::io::stdout().flush().ok();
+    let done = Arc::new(AtomicBool::new(false));
+    let done_flag = done.clone();
+    let progress_ctx = ctx.clone();
 
-        std::thread::sleep(Duration::from_millis(200));
-    }
+    let start_time = Instant::now();
 
-    let total = SCANNED_FILES.load(Ordering::Relaxed);
-    let elapsed = start_time.elapsed().as_secs_f64();
+    // Поток прогресса
+    let progress_thread = std::thread::spawn(move || {
+        // wide_msg сам обрезает строку по ширине терминала, поэтому путь ставим в конец
+        let bar = ProgressBar::new_spinner();
+        bar.set_style(
+            ProgressStyle::with_template("{spinner} {wide_msg}")
+                .unwrap()
+                .tick_chars("⠋⠙⠹⠸⠼⠴⠦⠧⠇⠏ "),
+        );
+        bar.enable_steady_tick(Duration::from_millis(100));
 
-    println!(
-        "\r✅ Готово: {} файлов за {:.1} сек (≈ {:.0} файлов/сек)",
-        total,
-        elapsed,
-        total as f64 / elapsed.max(0.001)
-    );
-});
+        // скорость усредняем по последним ~2 секундам, иначе она скачет от тика к тику
+        let mut samples: VecDeque<(Instant, u64)> = VecDeque::new();
 
-// Само сканирование
-let root = build_tree(&path, args.ignoreos5)?;
+        while !done_flag.load(Ordering::Relaxed) {
+            let count = progress_ctx.scanned_files.load(Ordering::Relaxed);
 
-// Сообщаем что всё
-done.store(true, Ordering::Relaxed);
-progress_thread.join().ok();
+            let now = Instant::now();
+            samples.push_back((now, count));
+            while samples.len() > 10 {
+                samples.pop_front();
+            }
+            let (first_time, first_count) = samples[0];
+            let dt = now.duration_since(first_time).as_secs_f64().max(0.001);
+            let speed = (count - first_count) as f64 / dt;
+
+            let eta = match expected_files {
+                Some(expected) if expected > count && speed > 0.0 => format!(
+                    " | ⏳ {} ~{:.0} {}",
+                    tr("осталось", "left"),
+                    (expected - count) as f64 / speed,
+                    tr("сек", "s")
+                ),
+                _ => String::new(),
+            };
 
-//
+            let path = CURRENT_PATH.lock().unwrap().clone();
+
+            bar.set_message(format!(
+                "📄 {}: {} | ⚡ {:.0} {}{} | ↧ {} {} | 📁 {}",
+                tr("файлов", "files"),
+                count,
+                speed,
+                tr("файлов/сек", "files/s"),
+                eta,
+                tr("глубина", "depth"),
+                CURRENT_DEPTH.load(Ordering::Relaxed),
+                path
+            ));
+
+            std::thread::sleep(Duration::from_millis(200));
+        }
+        // убираем полосу до итоговой строки и до входа в альтернативный экран
+        bar.finish_and_clear();
 
-    enable_raw_mode()?;
-    stdout().execute(EnterAlternateScreen)?.execute(EnableMouseCapture)?;
-    let backend = CrosstermBackend::new(stdout());
-    let mut terminal = Terminal::new(backend)?;
+        let total = progress_ctx.scanned_files.load(Ordering::Relaxed);
+        let elapsed = start_time.elapsed().as_secs_f64();
 
-    let mut app = App::new(root);
+        if progress_ctx.cancel.load(Ordering::Relaxed) {
+            if ENGLISH.load(Ordering::Relaxed) {
+                println!("⛔ Interrupted: {} files in {:.1} s, data is incomplete", total, elapsed);
+            } else {
+                println!("⛔ Прервано: {} файлов за {:.1} сек, данные неполные", total, elapsed);
+            }
+        } else if ENGLISH.load(Ordering::Relaxed) {
+            println!(
+                "✅ Done: {} files in {:.1} s (≈ {:.0} files/s)",
+                total,
+                elapsed,
+                total as f64 / elapsed.max(0.001)
+            );
+        } else {
+            println!(
+                "✅ Готово: {} файлов за {:.1} сек (≈ {:.0} файлов/сек)",
+                total,
+                elapsed,
+                total as f64 / elapsed.max(0.001)
+            );
+        }
+    });
+
+    let root = scan(path, opts, ctx);
+
+    // Сообщаем что всё
+    done.store(true, Ordering::Relaxed);
+    progress_thread.join().ok();
+
+    if let Ok(root) = &root {
+        let mut summary = format!(
+            "📦 {}: {}, {}: {}, {}: {}",
+            tr("файлов", "files"),
+            format_count(root.file_count),
+            tr("папок", "dirs"),
+            format_count(root.dir_count),
+            tr("всего", "total"),
+            format_size(root.size, units)
+        );
+        if let Some(largest) = largest_file(root) {
+            summary += &format!(
+                " | {}: {} ({})",
+                tr("самый большой файл", "largest file"),
+                largest.path.display(),
+                format_size(largest.size, units)
+            );
+        }
+        println!("{}", summary);
+        let errors = ctx.errors.lock().unwrap().len();
+        if errors > 0 {
+            println!("⚠️  {}", scan_errors_hint(errors));
+        }
+    }
 
-    loop {
-            //small optimizxcoDSAFNLKKLM'DBCVL;M
-        let size = terminal.size()?;
-        let area = Rect::new(0, 0, size.width, size.height);
-        app.ensure_layout(area);
-        
-        terminal.draw(|f| ui(f, &mut app))?;
+    root
+}
 
-        match event::read()? {
-            Event::Resize(_, _) => {
-                app.layout_dirty = true;
-            }
-            Event::Key(key) if key.kind == KeyEventKind::Press => match key.code {
-                KeyCode::Char('q') | KeyCode::Esc => break,
-                KeyCode::Enter => {
-                    if let Some(selected) = &app.selected {
-                        if let Some(node) = app.find_node(selected) {
-                            if node.is_dir && !node.children.is_empty() {
-                                app.current_dir = node.path.clone();
-                                app.offset_x = 0;
-                                app.offset_y = 0;
-                                app.layout_dirty = true;
-                            }
-                        }
-                    }
-                }
-                KeyCode::Char('h') | KeyCode::Left => {
-                    if app.scroll_mode {
+fn open_selected(terminal: &mut Terminal<CrosstermBackend<Stdout>>, app: &mut App) -> Result<()> {
+    let Some(node) = app.selected.as_ref().and_then(|p| app.find_node(p)) else {
+        return Ok(());
+    };
+    if node.is_dir {
+        if !node.children.is_empty() {
+            app.navigate_to(node.path.clone());
+        }
+        return Ok(());
+    }
+    if node.is_aggregated {
+        return Ok(());
+    }
+
+    let path = node.path.clone();
+    let editor = std::env::var("EDITOR").unwrap_or_default();
+    let result = if !editor.trim().is_empty() && is_text_file(&path) {
+        run_editor(terminal, &editor, &path)
+    } else {
+        open::that_detached(&path).map_err(Into::into)
+    };
+    if let Err(e) = result {
+        app.status_message = Some(format!("{} {}: {}", tr("Не удалось открыть", "Failed to open"), path.display(), e));
+    }
+    Ok(())
+}
+
+// текстом считаем файл без нулевых байтов в первых 8 КБ
+fn is_text_file(path: &Path) -> bool {
+    use std::io::Read;
+    let mut buf = [0u8; 8192];
+    match fs::File::open(path).and_then(|mut f| f.read(&mut buf)) {
+        Ok(n) => !buf[..n].contains(&0),
+        Err(_) => false,
+    }
+}
+
+// для паники: ошибки тут уже некуда сообщать, поэтому игнорируются
+fn restore_terminal() {
+    disable_raw_mode().ok();
+    stdout().execute(LeaveAlternateScreen).ok();
+    stdout().execute(DisableMouseCapture).ok();
+}
+
+fn run_editor(terminal: &mut Terminal<CrosstermBackend<Stdout>>, editor: &str, path: &Path) -> Result<()> {
+    let mut parts = editor.split_whitespace();
+    let program = parts.next().unwrap_or_default();
+
+    disable_raw_mode()?;
+    stdout().execute(LeaveAlternateScreen)?.execute(DisableMouseCapture)?;
+
+    let status = std::process::Command::new(program).args(parts).arg(path).status();
+
+    enable_raw_mode()?;
+    stdout().execute(EnterAlternateScreen)?.execute(EnableMouseCapture)?;
+    terminal.clear()?;
+
+    let status = status?;
+    if !status.success() {
+        anyhow::bail!("{} {} ({})", program, tr("завершился с ошибкой", "failed"), status);
+    }
+    Ok(())
+}
+
+// новое окно терминала в директории; $TERMINAL на Linux, Terminal.app на macOS, wt или cmd на Windows
+fn open_terminal(dir: &Path) -> Result<()> {
+    use std::process::{Command, Stdio};
+
+    #[cfg(target_os = "windows")]
+    let mut command = if Command::new("where").arg("wt").stdout(Stdio::null()).stderr(Stdio::null()).status().is_ok_and(|s| s.success()) {
+        let mut command = Command::new("wt");
+        command.arg("-d").arg(dir);
+        command
+    } else {
+        let mut command = Command::new("cmd");
+        command.args(["/C", "start", "cmd"]);
+        command
+    };
+
+    #[cfg(target_os = "macos")]
+    let mut command = {
+        let mut command = Command::new("open");
+        command.args(["-a", "Terminal"]).arg(dir);
+        command
+    };
+
+    #[cfg(not(any(target_os = "windows", target_os = "macos")))]
+    let mut command = Command::new(
+        std::env::var("TERMINAL").ok().filter(|t| !t.trim().is_empty()).unwrap_or_else(|| "x-terminal-emulator".to_string()),
+    );
+
+    // вывод терминала не должен попасть поверх интерфейса
+    command
+        .current_dir(dir)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()?;
+    Ok(())
+}
+
+// для окна прогресса: директория, которую сканер сейчас читает
+fn report_progress(path: &Path, depth: usize) {
+    *CURRENT_PATH.lock().unwrap() = path.display().to_string();
+    CURRENT_DEPTH.store(depth, Ordering::Relaxed);
+}
+
+// выполняет сканирование в отдельном потоке, пока рисуется окно прогресса; Esc выставляет ctx.cancel.
+// None, если поток упал
+fn scan_in_background<T: Send + 'static>(
+    terminal: &mut Terminal<CrosstermBackend<Stdout>>,
+    app: &mut App,
+    ctx: &Arc<ScanContext>,
+    job: impl FnOnce(&ScanOptions, &ScanContext) -> T + Send + 'static,
+) -> Result<Option<T>> {
+    let opts = app.scan_options.clone();
+    let scan_ctx = ctx.clone();
+    let handle = std::thread::spawn(move || job(&opts, &scan_ctx));
+    while !handle.is_finished() {
+        terminal.draw(|f| {
+            ui(f, app);
+            render_scan_progress(f, ctx);
+            downgrade_colors(f.buffer_mut());
+        })?;
+        // остальные клавиши во время сканирования не нужны
+        if event::poll(Duration::from_millis(100))?
+            && let Event::Key(key) = event::read()?
+            && key.kind == KeyEventKind::Press
+            && key.code == KeyCode::Esc
+        {
+            ctx.cancel.store(true, Ordering::Relaxed);
+        }
+    }
+
+    match handle.join() {
+        Ok(result) => Ok(Some(result)),
+        Err(_) => {
+            // сообщение паники напечатано поверх интерфейса, перерисовываем экран целиком
+            terminal.clear()?;
+            app.status_message = Some(tr("Сканирование завершилось с ошибкой", "Scan thread crashed").to_string());
+            Ok(None)
+        }
+    }
+}
+
+// false, если дерево осталось прежним: сканирование отменено или не удалось
+fn rescan(terminal: &mut Terminal<CrosstermBackend<Stdout>>, app: &mut App, path: PathBuf) -> Result<bool> {
+    let scan_path = path.clone();
+    let ctx = Arc::new(ScanContext::for_subtree(app.hardlinks.clone(), &path));
+    let Some(result) = scan_in_background(terminal, app, &ctx, move |opts, ctx| scan(&scan_path, opts, ctx))? else {
+        return Ok(false);
+    };
+    if ctx.cancel.load(Ordering::Relaxed) {
+        app.status_message = Some(tr("Пересканирование отменено", "Rescan cancelled").to_string());
+        return Ok(false);
+    }
+
+    match result {
+        Ok(mut node) => {
+            // первая точка истории — размер до пересканирования
+            if !app.size_history.contains_key(&path)
+                && let Some(old_size) = app.find_node(&path).map(|n| n.size)
+            {
+                app.record_size(&path, old_size);
+            }
+            app.record_size(&path, node.size);
+            sort_tree(&mut node, app.sort_mode, app.scan_options.dirs_first);
+            app.replace_node(node);
+            app.take_scan_results(&path, &ctx);
+            app.selected = None;
+            app.selected_tile = None;
+            app.invalidate_layout();
+            // после пересканирования корня текущая директория могла исчезнуть
+            let current_dir = app.current_dir.clone();
+            app.navigate_to(if app.find_node(&current_dir).is_some() { current_dir } else { path.clone() });
+            let mut message = format!(
+                "{}: {} ({}: {})",
+                tr("Пересканировано", "Rescanned"),
+                path.display(),
+                tr("файлов", "files"),
+                ctx.scanned_files.load(Ordering::Relaxed)
+            );
+            if !app.scan_errors.is_empty() {
+                message += &format!(" | {}", scan_errors_hint(app.scan_errors.len()));
+            }
+            app.status_message = Some(message);
+            Ok(true)
+        }
+        Err(e) => {
+            app.status_message = Some(format!("{}: {}", tr("Не удалось пересканировать", "Rescan failed"), e));
+            Ok(false)
+        }
+    }
+}
+
+// --watch: запись, которую надо обновить ради изменения path, — ребёнок ближайшей директории из дерева.
+// Внутри новой директории это она сама, за лимитом --max-depth — листовая директория на границе
+fn changed_entry(app: &App, path: &Path) -> Option<PathBuf> {
+    let mut entry = path;
+    loop {
+        let dir = entry.parent()?;
+        if app.find_node(dir).is_some_and(|n| n.is_dir) {
+            let depth = dir.strip_prefix(&app.scan_options.scan_root).map_or(0, |rel| rel.components().count());
+            if app.scan_options.max_depth.is_some_and(|max| depth > max) {
+                return Some(dir.to_path_buf());
+            }
+            return Some(entry.to_path_buf());
+        }
+        entry = dir;
+    }
+}
+
+// файлы обновляются одним stat, директории, которых ещё нет в дереве, сканируются в фоне
+fn refresh_changed(terminal: &mut Terminal<CrosstermBackend<Stdout>>, app: &mut App, changed: HashSet<PathBuf>) -> Result<()> {
+    let follow_symlinks = app.scan_options.follow_symlinks;
+    let is_dir = |path: &Path| {
+        let metadata = if follow_symlinks { fs::metadata(path) } else { fs::symlink_metadata(path) };
+        metadata.is_ok_and(|m| m.is_dir())
+    };
+    // события самой директории из дерева ничего не говорят: изменения её содержимого приходят отдельно
+    let mut entries: Vec<PathBuf> = changed.iter()
+        .filter_map(|p| changed_entry(app, p))
+        .filter(|e| !(changed.contains(e) && app.find_node(e).is_some_and(|n| n.is_dir) && is_dir(e)))
+        .collect();
+    entries.sort();
+    entries.dedup();
+    // вложенные записи обновятся вместе с родительской
+    let entries: Vec<PathBuf> = entries.iter()
+        .filter(|e| !entries.iter().any(|other| other != *e && e.starts_with(other)))
+        .cloned()
+        .collect();
+    if entries.is_empty() {
+        return Ok(());
+    }
+
+    for entry in entries {
+        if !is_dir(&entry) {
+            // файл или пропавшая запись; если её нет, обновит следующее событие
+            let ctx = ScanContext::for_subtree(app.hardlinks.clone(), &entry);
+            if let Ok(node) = scan_entry(&entry, &app.scan_options, &ctx) {
+                app.update_entry(&entry, node);
+                app.take_scan_results(&entry, &ctx);
+            }
+            continue;
+        }
+        let scan_path = entry.clone();
+        let ctx = Arc::new(ScanContext::for_subtree(app.hardlinks.clone(), &entry));
+        let result = scan_in_background(terminal, app, &ctx, move |opts, ctx| scan_entry(&scan_path, opts, ctx))?;
+        if ctx.cancel.load(Ordering::Relaxed) {
+            app.status_message = Some(format!("{}: {}", tr("Обновление отменено", "Refresh cancelled"), entry.display()));
+            continue;
+        }
+        if let Some(Ok(node)) = result {
+            app.update_entry(&entry, node);
+            app.take_scan_results(&entry, &ctx);
+        }
+    }
+
+    if app.selected.as_ref().is_some_and(|p| app.find_node(p).is_none()) {
+        app.selected = None;
+        app.selected_tile = None;
+    }
+    let mut current_dir = app.current_dir.clone();
+    while app.find_node(&current_dir).is_none() && current_dir.pop() {}
+    if current_dir != app.current_dir {
+        app.navigate_to(current_dir);
+    }
+    app.invalidate_layout();
+    Ok(())
+}
+
+fn main() -> Result<()> {
+    let args = Args::parse();
+    if let Some(shell) = args.generate_completions {
+        clap_complete::generate(shell, &mut Args::command(), env!("CARGO_PKG_NAME"), &mut stdout());
+        return Ok(());
+    }
+    ENGLISH.store(args.lang == Lang::En, Ordering::Relaxed);
+    let units = args.units;
+    if let Some(file) = &args.theme {
+        THEME.set(load_theme(file)?).ok();
+    }
+    // https://no-color.org: пустая NO_COLOR не считается
+    let monochrome = args.no_color || std::env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty());
+    let support = if monochrome {
+        ColorSupport::Monochrome
+    } else if args.color == ColorSupport::Auto {
+        detect_color_support()
+    } else {
+        args.color
+    };
+    if support != ColorSupport::Truecolor {
+        COLOR_SUPPORT.set(support).ok();
+    }
+    // на Windows canonicalize отказывает части сетевых и виртуальных дисков, тогда хватит абсолютного пути
+    let canonical = args.path.canonicalize().or_else(|e| match std::path::absolute(&args.path) {
+        Ok(absolute) if cfg!(windows) && absolute.exists() => Ok(absolute),
+        _ => Err(e),
+    });
+    let Ok(mut path) = canonical.map(|p| long_path(&p).into_owned()) else {
+        eprintln!(
+            "{}: {}",
+            tr("Путь не существует или недоступен", "Path does not exist or is not accessible"),
+            args.path.display()
+        );
+        std::process::exit(1);
+    };
+    // импорт и снимки путь не сканируют, им хватает того, что он есть
+    let scans_path = args.compare.is_none() && args.import_ncdu.is_none() && args.import_du.is_none() && args.load_cache.is_none();
+    // для файла сканируем его директорию и сразу выбираем его самого
+    let mut target_file = None;
+    if scans_path && path.is_file()
+        && let Some(parent) = path.parent()
+    {
+        let parent = parent.to_path_buf();
+        target_file = Some(std::mem::replace(&mut path, parent));
+    }
+    // сам симлинк, а не его цель; для файла корнем станет его директория, и имя уже не подойдёт
+    let root_alias = if args.follow_root_only && target_file.is_none() && args.path.symlink_metadata().is_ok_and(|m| m.is_symlink()) {
+        std::path::absolute(&args.path).ok()
+    } else {
+        None
+    };
+    if scans_path && !path.is_dir() {
+        eprintln!("{}: {}", tr("Это не директория", "Not a directory"), path.display());
+        std::process::exit(1);
+    }
+
+    if let Some(threads) = args.threads {
+        rayon::ThreadPoolBuilder::new()
+            .num_threads(threads.max(1))
+            .build_global()?;
+    }
+    let mut exclude_patterns = args.exclude.clone();
+    if let Some(file) = &args.exclude_from {
+        exclude_patterns.extend(read_exclude_file(file)?);
+    }
+    let opts = ScanOptions {
+        ignore_permission_errors: args.ignore_permission_errors,
+        max_depth: args.max_depth,
+        follow_symlinks: args.follow_symlinks,
+        count_hardlinks_once: args.count_hardlinks_once,
+        disk_usage: args.disk_usage,
+        root_dev: if args.one_file_system { fs::metadata(&path).ok().and_then(|m| device_id(&m)) } else { None },
+        min_size: args.min_size,
+        no_other: args.no_other,
+        other_factors: args.other_factors,
+        dirs_first: args.dirs_first,
+        respect_gitignore: args.respect_gitignore,
+        scan_root: path.clone(),
+        exclude: build_globset(&exclude_patterns)?,
+        exclude_hidden: args.exclude_hidden,
+        name_filter: args.name_filter.clone(),
+        exclude_empty: args.exclude_empty,
+        other_name: Some(tr("Прочее", "Other").to_string()),
+        profile: args.profile,
+        progress: Some(Arc::new(report_progress)),
+    };
+
+    // счётчики и ошибки первого сканирования; при загрузке кэша или импорте остаётся пустым
+    let scan_ctx = Arc::new(ScanContext::default());
+    let mut startup_message = None;
+    let mut compare_base = None;
+    let root = if let Some([before, after]) = args.compare.as_deref() {
+        let mut sizes = HashMap::new();
+        collect_sizes(&load_cache(before)?.tree, &mut sizes);
+        compare_base = Some(sizes);
+        load_cache(after)?.tree
+    } else if let Some(file) = &args.import_ncdu {
+        import_ncdu(file, &opts)?
+    } else if let Some(file) = &args.import_du {
+        import_du(file, &opts)?
+    } else if let Some(cache_path) = &args.load_cache {
+        let cache = load_cache(cache_path)?;
+        let age = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs())
+            .saturating_sub(cache.header.created);
+        if !args.quiet {
+            if ENGLISH.load(Ordering::Relaxed) {
+                println!("Loaded cache {} (created {} s ago)", cache_path.display(), age);
+            } else {
+                println!("Загружен кэш {} (создан {} сек назад)", cache_path.display(), age);
+            }
+        }
+        if cache.header.root != path {
+            let warning = if ENGLISH.load(Ordering::Relaxed) {
+                format!("Warning: cache was built for {}, not {}", cache.header.root.display(), path.display())
+            } else {
+                format!("Внимание: кэш построен для {}, а не для {}", cache.header.root.display(), path.display())
+            };
+            // в stderr, чтобы не смешиваться с выводом --print-tree и --top
+            eprintln!("{}", warning);
+            startup_message = Some(warning);
+        }
+        cache.tree
+    } else {
+        // первый Ctrl+C останавливает сканирование, второй завершает программу
+        let cancel_ctx = scan_ctx.clone();
+        ctrlc::set_handler(move || {
+            if cancel_ctx.cancel.swap(true, Ordering::Relaxed) {
+                std::process::exit(130);
+            }
+        })?;
+        // прошлый кэш того же корня даёт оценку числа файлов для ETA
+        let expected_files = args.save_cache.as_deref()
+            .and_then(|file| load_cache_header(file).ok())
+            .filter(|header| header.root == path)
+            .map(|header| header.file_count);
+        let scan_start = Instant::now();
+        let root = scan_with_progress(&path, &opts, &scan_ctx, expected_files, args.quiet, units)?;
+        if args.profile {
+            print_profile(scan_start.elapsed(), &scan_ctx);
+        }
+        // incomplete бывает и у нечитаемых директорий, прерывание видно только по флагу отмены
+        if scan_ctx.cancel.load(Ordering::Relaxed) {
+            startup_message = Some(tr("Сканирование прервано, показаны неполные данные", "Scan interrupted, showing partial data").to_string());
+        }
+        root
+    };
+
+    if let Some(cache_path) = &args.save_cache {
+        save_cache(cache_path, &path, &root)?;
+    }
+    if let Some(export_path) = &args.export_ncdu {
+        export_ncdu(export_path, &root)?;
+    }
+    if let Some(export_path) = &args.export_csv {
+        export_csv(export_path, &root)?;
+    }
+
+    if args.find_dupes {
+        let groups = find_duplicates(&root);
+        let mut out = BufWriter::new(stdout().lock());
+        let mut wasted_total = 0;
+        for (size, paths) in &groups {
+            let wasted = size * (paths.len() as u64 - 1);
+            wasted_total += wasted;
+            writeln!(
+                out,
+                "{} {}: {} × {}",
+                format_size(wasted, units),
+                tr("лишних", "wasted"),
+                paths.len(),
+                format_size(*size, units)
+            )?;
+            for path in paths {
+                writeln!(out, "    {}", path.display())?;
+            }
+        }
+        writeln!(
+            out,
+            "{}: {}, {}: {}",
+            tr("Групп дубликатов", "Duplicate groups"),
+            groups.len(),
+            tr("можно освободить", "reclaimable"),
+            format_size(wasted_total, units)
+        )?;
+        out.flush()?;
+        return Ok(());
+    }
+
+    if let Some(count) = args.top {
+        let mut out = BufWriter::new(stdout().lock());
+        for (size, path) in largest_files(&root, count) {
+            writeln!(out, "{:>10}  {}", format_size(size, units), path.display())?;
+        }
+        out.flush()?;
+        return Ok(());
+    }
+
+    if args.print_tree {
+        let mut out = BufWriter::new(stdout().lock());
+        print_tree(&mut out, &root, 0, args.depth, args.warn_size, units)?;
+        out.flush()?;
+        // корень включает всё дерево: если бюджет превысил хоть кто-то, превысил и он
+        if let Some(limit) = args.warn_size
+            && root.size > limit
+        {
+            eprintln!("⚠️  {}: {} > {}", tr("Превышен бюджет размера", "Size budget exceeded"), format_size(root.size, units), format_size(limit, units));
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+
+    // при панике сначала возвращаем терминал, иначе сообщение потеряется в альтернативном экране.
+    // Паника потока сканирования ловится в rescan, и интерфейс продолжает работать в raw mode
+    let main_thread = std::thread::current().id();
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        if std::thread::current().id() == main_thread {
+            restore_terminal();
+        }
+        default_hook(info);
+    }));
+    enable_raw_mode()?;
+    stdout().execute(EnterAlternateScreen)?.execute(EnableMouseCapture)?;
+    let backend = CrosstermBackend::new(stdout());
+    let mut terminal = Terminal::new(backend)?;
+
+    let mut root = root;
+    if let Some(name) = root_alias.as_ref().and_then(|alias| alias.file_name()) {
+        root.name = name.to_string_lossy().into_owned();
+    }
+    // подпись показывается как путь корня, вложенные пути — относительно неё
+    let root_alias = match &args.root_label {
+        Some(label) => {
+            root.name = label.clone();
+            Some(PathBuf::from(label))
+        }
+        None => root_alias,
+    };
+    let mut app = App::new(root);
+    app.root_alias = root_alias;
+    app.disk_usage = args.disk_usage;
+    app.units = args.units;
+    app.layout_algorithm = args.layout;
+    app.min_tile = args.min_tile;
+    app.render_depth = args.render_depth;
+    app.imported = args.import_ncdu.is_some() || args.import_du.is_some() || args.compare.is_some();
+    app.read_only = args.no_delete || app.imported;
+    app.show_icons = !args.no_icons;
+    app.animate = !args.no_animation;
+    app.dense = args.dense;
+    app.monochrome = monochrome;
+    app.warn_size = args.warn_size;
+    // для импортированного дерева диск этой машины ничего не говорит
+    if !app.imported {
+        app.filesystem_space = filesystem_space(&path);
+    }
+    app.take_scan_results(&path, &scan_ctx);
+    if startup_message.is_none() && !app.scan_errors.is_empty() {
+        startup_message = Some(scan_errors_hint(app.scan_errors.len()));
+    }
+    app.status_message = startup_message;
+    app.scan_options = opts;
+    if compare_base.is_some() {
+        app.compare_base = compare_base;
+        app.color_mode = ColorMode::Delta;
+    }
+    if let Some(file) = target_file {
+        app.selected = Some(file);
+    } else {
+        restore_state(&mut app);
+    }
+
+    let (watch_tx, watch_rx) = std::sync::mpsc::channel();
+    let mut watcher = if args.watch { Some(notify::recommended_watcher(watch_tx)?) } else { None };
+    let mut watched_dir: Option<PathBuf> = None;
+    let mut changed: HashSet<PathBuf> = HashSet::new();
+    let mut first_change = Instant::now();
+    let mut last_change = Instant::now();
+    let redraw_interval = Duration::from_millis(args.redraw_interval);
+    // без событий не перерисовываем: в простое программа только ждёт ввода
+    let mut redraw = true;
+
+    loop {
+            //small optimizxcoDSAFNLKKLM'DBCVL;M
+        if let Some(watcher) = &mut watcher
+            && watched_dir.as_deref() != Some(real_dir(&app.current_dir))
+        {
+            if let Some(old) = watched_dir.take() {
+                watcher.unwatch(&old).ok();
+            }
+            let dir = real_dir(&app.current_dir).to_path_buf();
+            if let Err(e) = watcher.watch(&dir, RecursiveMode::Recursive) {
+                app.status_message = Some(format!("{} {}: {}", tr("Не удалось следить за", "Failed to watch"), dir.display(), e));
+            }
+            watched_dir = Some(dir);
+        }
+        for event in watch_rx.try_iter().flatten() {
+            if matches!(
+                event.kind,
+                EventKind::Create(_) | EventKind::Remove(_) | EventKind::Modify(ModifyKind::Data(_) | ModifyKind::Name(_) | ModifyKind::Any)
+            ) {
+                if changed.is_empty() {
+                    first_change = Instant::now();
+                }
+                changed.extend(event.paths);
+                last_change = Instant::now();
+            }
+        }
+        // события приходят пачками: обновляем, когда они затихли или копятся слишком долго
+        if !changed.is_empty() && (last_change.elapsed() >= WATCH_DEBOUNCE || first_change.elapsed() >= WATCH_MAX_WAIT) {
+            refresh_changed(&mut terminal, &mut app, std::mem::take(&mut changed))?;
+            redraw = true;
+        }
+
+        if redraw {
+            let size = terminal.size()?;
+            let area = Rect::new(0, 0, size.width, size.height);
+            let (_, main_area, _, _) = screen_areas(area, app.show_legend);
+            app.ensure_layout(main_area);
+            app.clamp_offsets();
+
+            terminal.draw(|f| {
+                ui(f, &mut app);
+                downgrade_colors(f.buffer_mut());
+            })?;
+            redraw = false;
+        }
+
+        let timeout = if app.zoom.is_some() { ZOOM_FRAME } else { redraw_interval };
+        if !event::poll(timeout)? {
+            // следующий кадр анимации
+            if app.zoom.is_some() {
+                redraw = true;
+            }
+            continue;
+        }
+        redraw = true;
+        match event::read()? {
+            Event::Resize(_, _) => {
+                app.layout_dirty = true;
+            }
+            Event::Key(key) if key.kind == KeyEventKind::Press && app.show_errors => match key.code {
+                KeyCode::Up | KeyCode::Char('k') => app.errors_scroll = app.errors_scroll.saturating_sub(1),
+                KeyCode::Down | KeyCode::Char('j') => {
+                    app.errors_scroll = (app.errors_scroll + 1).min(app.scan_errors.len().saturating_sub(1));
+                }
+                KeyCode::Char('E') | KeyCode::Char('q') | KeyCode::Esc => app.show_errors = false,
+                _ => {}
+            },
+            Event::Key(key) if key.kind == KeyEventKind::Press && app.show_histogram => {
+                if matches!(key.code, KeyCode::Char('g') | KeyCode::Char('q') | KeyCode::Esc) {
+                    app.show_histogram = false;
+                }
+            }
+            Event::Key(key) if key.kind == KeyEventKind::Press && app.details.is_some() => match key.code {
+                KeyCode::Up | KeyCode::Char('k') => app.details_scroll = app.details_scroll.saturating_sub(1),
+                KeyCode::Down | KeyCode::Char('j') => app.details_scroll = app.details_scroll.saturating_add(1),
+                KeyCode::Char('i') | KeyCode::Char('q') | KeyCode::Esc => app.details = None,
+                _ => {}
+            },
+            Event::Key(key) if key.kind == KeyEventKind::Press && app.show_help => {
+                if matches!(key.code, KeyCode::Char('?') | KeyCode::Char('q') | KeyCode::Esc) {
+                    app.show_help = false;
+                }
+            }
+            Event::Key(key) if key.kind == KeyEventKind::Press && app.pending_delete.is_some() => {
+                if key.code == KeyCode::Char('y') {
+                    app.confirm_delete();
+                } else {
+                    app.pending_delete = None;
+                }
+            }
+            Event::Key(key) if key.kind == KeyEventKind::Press && app.search_mode => match key.code {
+                KeyCode::Esc => {
+                    app.search_mode = false;
+                    app.search_query.clear();
+                }
+                KeyCode::Enter => {
+                    app.search_mode = false;
+                    app.search_index = 0;
+                }
+                KeyCode::Backspace => {
+                    app.search_query.pop();
+                }
+                KeyCode::Char(c) => app.search_query.push(c),
+                _ => {}
+            },
+            Event::Key(key) if key.kind == KeyEventKind::Press => {
+                app.status_message = None;
+                match key.code {
+                    KeyCode::Esc if !app.search_query.is_empty() => app.search_query.clear(),
+                    KeyCode::Char('q') | KeyCode::Esc => break,
+                    KeyCode::Char('/') => {
+                        app.search_mode = true;
+                        app.search_query.clear();
+                    }
+                    KeyCode::Char('n') if !app.search_query.is_empty() => app.select_next_match(),
+                    KeyCode::Char('d') => app.request_delete(),
+                    KeyCode::Char('?') => app.show_help = true,
+                    KeyCode::Char('E') => app.show_errors = true,
+                    KeyCode::Char('g') => app.show_histogram = true,
+                    KeyCode::Char('i') => app.open_details(),
+                    KeyCode::Char('e') => app.show_legend = !app.show_legend,
+                    KeyCode::Char('b') => {
+                        app.group_by_extension = !app.group_by_extension;
+                        app.invalidate_layout();
+                    }
+                    KeyCode::Char('.') => {
+                        app.collapse_chains = !app.collapse_chains;
+                        app.invalidate_layout();
+                    }
+                    KeyCode::Char('p') => app.show_paths = !app.show_paths,
+                    KeyCode::Char('B') => app.dense = !app.dense,
+                    KeyCode::Char('v') => app.list_view = !app.list_view,
+                    KeyCode::Char('F') | KeyCode::Char('D') => {
+                        let filter = if key.code == KeyCode::Char('F') { ViewFilter::Files } else { ViewFilter::Dirs };
+                        app.view_filter = if app.view_filter == filter { ViewFilter::All } else { filter };
+                        app.invalidate_layout();
+                    }
+                    KeyCode::Char('c') => {
+                        // у файла открываем его директорию; группа по расширению лежит в текущей
+                        let dir = match app.selected.as_ref().and_then(|p| app.find_node(p)) {
+                            Some(node) if node.is_dir => real_dir(&node.path).to_path_buf(),
+                            Some(node) => node.path.parent().map_or_else(|| app.current_dir.clone(), Path::to_path_buf),
+                            None => real_dir(&app.current_dir).to_path_buf(),
+                        };
+                        if let Err(e) = open_terminal(&dir) {
+                            app.status_message = Some(format!("{} {}: {}", tr("Не удалось открыть терминал в", "Failed to open a terminal in"), dir.display(), e));
+                        }
+                    }
+                    KeyCode::Char('+') | KeyCode::Char('=') => app.change_render_depth(1),
+                    KeyCode::Char('-') => app.change_render_depth(-1),
+                    KeyCode::Char('s') => app.cycle_sort_mode(),
+                    KeyCode::Char('f') => app.toggle_dirs_first(),
+                    KeyCode::Char('u') => app.toggle_disk_usage(),
+                    KeyCode::Char('r') => {
+                        if app.can_rescan() {
+                            let path = real_dir(&app.current_dir).to_path_buf();
+                            rescan(&mut terminal, &mut app, path)?;
+                        }
+                    }
+                    KeyCode::Char('a') => {
+                        // скрытые записи не хранятся в дереве, поэтому пересчитываем его целиком
+                        if app.can_rescan() {
+                            app.scan_options.exclude_hidden = !app.scan_options.exclude_hidden;
+                            let root = app.root.path.clone();
+                            if !rescan(&mut terminal, &mut app, root)? {
+                                app.scan_options.exclude_hidden = !app.scan_options.exclude_hidden;
+                            }
+                        }
+                    }
+                    KeyCode::Char('o') => open_selected(&mut terminal, &mut app)?,
+                    KeyCode::Char('t') => {
+                        app.color_mode = match app.color_mode {
+                            ColorMode::Extension => ColorMode::Age,
+                            ColorMode::Age if app.compare_base.is_some() => ColorMode::Delta,
+                            _ => ColorMode::Extension,
+                        };
+                    }
+                    KeyCode::Char('y') => {
+                        if let Some(selected) = &app.selected {
+                            // у "Прочее" и групп по расширению нет пути на диске, копируем их директорию;
+                            // без префикса \\?\, который вставят не все программы
+                            let text = display_path(real_dir(selected)).display().to_string();
+                            // копируется настоящий путь, а в статусе он, как и везде, под --root-label
+                            let shown = app.shown_path(real_dir(selected)).display().to_string();
+                            app.status_message = Some(match copy_to_clipboard(&text) {
+                                Ok(()) => format!("{}: {}", tr("Скопировано", "Copied"), shown),
+                                Err(e) => format!("{}: {}", tr("Не удалось скопировать", "Copy failed"), e),
+                            });
+                        }
+                    }
+                    KeyCode::Enter => {
+                        if let Some(node) = app.selected.as_ref().and_then(|p| app.find_node(p))
+                            && node.is_dir && !node.children.is_empty()
+                        {
+                            app.zoom_into(node.path.clone());
+                        }
+                    }
+                    KeyCode::Backspace => {
+                        if app.current_dir != app.root.path
+                            && let Some(parent) = app.current_dir.parent()
+                        {
+                            app.navigate_to(parent.to_path_buf());
+                        }
+                    }
+                    KeyCode::Home => {
+                        app.navigate_to(app.root.path.clone());
+                        app.selected = None;
+                        app.selected_tile = None;
+                    }
+                    KeyCode::Char('k') | KeyCode::Up if app.list_view => app.move_list_selection(-1),
+                    KeyCode::Char('j') | KeyCode::Down if app.list_view => app.move_list_selection(1),
+                    KeyCode::Char(c @ '1'..='9') if app.list_view => app.open_ranked(c as usize - '1' as usize),
+                    KeyCode::Char('h') | KeyCode::Left if !app.scroll_mode => app.move_selection(-1, 0),
+                    KeyCode::Char('l') | KeyCode::Right if !app.scroll_mode => app.move_selection(1, 0),
+                    KeyCode::Char('k') | KeyCode::Up if !app.scroll_mode => app.move_selection(0, -1),
+                    KeyCode::Char('j') | KeyCode::Down if !app.scroll_mode => app.move_selection(0, 1),
+                    KeyCode::Char('h') | KeyCode::Left => {
                         app.offset_x = app.offset_x.saturating_sub(5);
-                    } else if let Some(parent) = app.current_dir.parent() {
-                        app.current_dir = parent.to_path_buf();
-                        app.offset_x = 0;
-                        app.offset_y = 0;
-                        app.layout_dirty = true;
                     }
-                }
-                KeyCode::Char('l') | KeyCode::Right => {
-                    if app.scroll_mode {
+                    KeyCode::Char('l') | KeyCode::Right => {
                         app.offset_x = app.offset_x.saturating_add(5);
                     }
-                }
-                KeyCode::Char('k') | KeyCode::Up => {
-                    if app.scroll_mode {
+                    KeyCode::Char('k') | KeyCode::Up => {
                         app.offset_y = app.offset_y.saturating_sub(3);
                     }
-                }
-                KeyCode::Char('j') | KeyCode::Down => {
-                    if app.scroll_mode {
+                    KeyCode::Char('j') | KeyCode::Down => {
                         app.offset_y = app.offset_y.saturating_add(3);
                     }
-                }
-                KeyCode::Char('H') => {
-                    if app.scroll_mode {
+                    KeyCode::Char('H') if app.scroll_mode => {
                         app.offset_x = app.offset_x.saturating_sub(20);
                     }
-                }
-                KeyCode::Char('L') => {
-                    if app.scroll_mode {
+                    KeyCode::Char('L') if app.scroll_mode => {
                         app.offset_x = app.offset_x.saturating_add(20);
                     }
-                }
-                KeyCode::Char('K') => {
-                    if app.scroll_mode {
+                    KeyCode::Char('K') if app.scroll_mode => {
                         app.offset_y = app.offset_y.saturating_sub(10);
                     }
-                }
-                KeyCode::Char('J') => {
-                    if app.scroll_mode {
+                    KeyCode::Char('J') if app.scroll_mode => {
                         app.offset_y = app.offset_y.saturating_add(10);
                     }
+                    KeyCode::Char('0') if app.scroll_mode => {
+                        app.offset_x = 0;
+                        app.offset_y = 0;
+                    }
+                    KeyCode::Char('z') if app.scroll_mode => app.center_on_selected(),
+                    _ => {}
                 }
-                _ => {}
-            },
+            }
             Event::Mouse(mouse) => match mouse.kind {
-                MouseEventKind::Moved => {
-                    app.mouse_pos = (mouse.column, mouse.row);
-                    app.selected = app.get_node_at(mouse.column, mouse.row).map(|n| n.path.clone());
+                // в списке плитки не нарисованы, наводить не на что
+                MouseEventKind::Moved if !app.list_view => {
+                    app.select_tile(app.tile_at(mouse.column, mouse.row));
                 }
                 MouseEventKind::Down(_) => {
-                    if let Some(node) = app.get_node_at(mouse.column, mouse.row) {
+                    if let Some(path) = app.breadcrumb_at(mouse.column, mouse.row) {
+                        app.navigate_to(path);
+                    } else if app.jump_to_minimap(mouse.column, mouse.row) {
+                        // видимая область уже перенесена
+                    } else if !app.list_view
+                        && let Some(node) = app.get_tile_at(mouse.column, mouse.row)
+                    {
                         // проверяем, нажата ли Ctrl
                         if mouse.modifiers.contains(KeyModifiers::CONTROL) {
-                            // открываем в файловом менеджере
-                            open_in_file_manager(&node.path);
-                        } else if node.is_dir && !node.children.is_empty() {
-                            app.current_dir = node.path.clone();
-                            app.offset_x = 0;
-                            app.offset_y = 0;
-                            app.layout_dirty = true;
+                            // открываем в файловом менеджере; у "Прочее" это его директория
+                            open_in_file_manager(real_dir(&node.path));
+                        } else if node.is_dir && node.has_children {
+                            app.zoom_into(node.path.clone());
                         }
                     }
                 }
-
-
+                // колесо прокручивает холст так же, как клавиши; Shift+колесо — по горизонтали
+                MouseEventKind::ScrollUp | MouseEventKind::ScrollDown if app.scroll_mode => {
+                    let up = mouse.kind == MouseEventKind::ScrollUp;
+                    if mouse.modifiers.contains(KeyModifiers::SHIFT) {
+                        app.offset_x = if up { app.offset_x.saturating_sub(5) } else { app.offset_x.saturating_add(5) };
+                    } else {
+                        app.offset_y = if up { app.offset_y.saturating_sub(3) } else { app.offset_y.saturating_add(3) };
+                    }
+                }
+                MouseEventKind::ScrollLeft if app.scroll_mode => {
+                    app.offset_x = app.offset_x.saturating_sub(5);
+                }
+                MouseEventKind::ScrollRight if app.scroll_mode => {
+                    app.offset_x = app.offset_x.saturating_add(5);
+                }
                 _ => {}
             },
             _ => {}
@@ -694,24 +2865,291 @@ progress_thread.join().ok();
 
     disable_raw_mode()?;
     stdout().execute(LeaveAlternateScreen)?.execute(DisableMouseCapture)?;
+    // состояние не критично: не удалось записать — просто не восстановим
+    save_state(&app).ok();
+    // только после выхода из альтернативного экрана, иначе строка пропадёт вместе с ним
+    if args.print_cwd_on_exit {
+        // подпись из --root-label не путь, cd по ней не перейти
+        let dir = real_dir(&app.current_dir);
+        let shown = if args.root_label.is_some() { display_path(dir) } else { app.shown_path(dir) };
+        println!("{}", shown.display());
+    }
     Ok(())
 }
 
-fn ui(f: &mut Frame, app: &mut App) {
+fn name_matches(name: &str, query_lower: &str) -> bool {
+    !query_lower.is_empty() && name.to_lowercase().contains(query_lower)
+}
+
+// длинный путь обрезаем слева: конец пути важнее
+fn truncate_start(text: &str, max_width: usize) -> String {
+    if Span::raw(text).width() <= max_width {
+        return text.to_string();
+    }
+    let tail = take_width(text.chars().rev(), max_width.saturating_sub(1));
+    std::iter::once('…').chain(tail.chars().rev()).collect()
+}
+
+// длинное имя обрезаем посередине, сохраняя расширение: "verylongfile…name.ext"
+fn truncate_middle(text: &str, max_width: usize) -> String {
+    if Span::raw(text).width() <= max_width {
+        return text.to_string();
+    }
+    let budget = max_width.saturating_sub(1);
+    let ext_width = match text.rfind('.') {
+        Some(dot) if dot > 0 => Span::raw(&text[dot..]).width(),
+        _ => 0,
+    };
+    let tail_width = ext_width.max(budget / 3).min(budget / 2);
+    let tail = take_width(text.chars().rev(), tail_width);
+    let head = take_width(text.chars(), budget - Span::raw(tail.as_str()).width());
+    let tail: String = tail.chars().rev().collect();
+    format!("{}…{}", head, tail)
+}
+
+// символы с начала итератора, пока влезают в ширину
+fn take_width(chars: impl Iterator<Item = char>, max_width: usize) -> String {
+    let mut out = String::new();
+    let mut width = 0;
+    for c in chars {
+        let char_width = Span::raw(c.to_string()).width();
+        if width + char_width > max_width {
+            break;
+        }
+        width += char_width;
+        out.push(c);
+    }
+    out
+}
+
+fn format_size(size: u64, units: Units) -> String {
+    let (base, options) = match units {
+        Units::Binary => (1024, BINARY),
+        Units::Decimal => (1000, DECIMAL),
+    };
+    if size < base {
+        format!("{} {}", size, tr("байт", "B"))
+    } else {
+        SizeFormatter::new(size, options).to_string()
+    }
+}
+
+fn format_count(n: u64) -> String {
+    let digits = n.to_string();
+    let mut out = String::new();
+    for (i, c) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            out.push(' ');
+        }
+        out.push(c);
+    }
+    out
+}
+
+// (хлебные крошки, плитки, легенда, статус); легенда нулевой ширины, если скрыта
+fn screen_areas(area: Rect, show_legend: bool) -> (Rect, Rect, Rect, Rect) {
     let chunks = Layout::default()
         .direction(Direction::Vertical)
-        .constraints([Constraint::Min(0), Constraint::Length(5)])
-        .split(f.area());
+        .constraints([Constraint::Length(1), Constraint::Min(0), Constraint::Length(5)])
+        .split(area);
+    let legend_width = if show_legend { 30 } else { 0 };
+    let middle = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Min(0), Constraint::Length(legend_width)])
+        .split(chunks[1]);
+    (chunks[0], middle[0], middle[1], chunks[2])
+}
+
+fn render_legend(f: &mut Frame, app: &mut App, area: Rect) {
+    let units = app.units;
+    // обход поддерева дорогой, поэтому пересчитываем только при смене директории или дерева
+    if app.legend.as_ref().is_none_or(|(dir, _)| *dir != app.current_dir) {
+        let node = app.find_node(&app.current_dir).unwrap_or(&app.root);
+        app.legend = Some((app.current_dir.clone(), extension_totals(node)));
+    }
+    let entries = app.legend.as_ref().map_or(&[][..], |(_, entries)| entries.as_slice());
+    let lines: Vec<Line> = entries
+        .iter()
+        .map(|(ext, size, _)| {
+            let color = color_for_extension(Some(ext));
+            let label = if ext.is_empty() { tr("(без расширения)", "(no extension)").to_string() } else { format!(".{}", ext) };
+            Line::from(vec![
+                Span::styled("██ ", Style::default().fg(color)),
+                Span::raw(format!("{} {}", label, format_size(*size, units))),
+            ])
+        })
+        .collect();
+
+    let legend = Paragraph::new(lines)
+        .style(Style::default().bg(Color::Rgb(20, 20, 30)).fg(Color::White))
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .border_style(Style::default().fg(Color::DarkGray))
+                .title(tr("Легенда (e)", "Legend (e)")),
+        );
+    f.render_widget(legend, area);
+}
+
+fn tile_color(app: &App, node: &TileInfo, total_size: u64, now: SystemTime, max_delta: u64) -> Color {
+    match app.color_mode {
+        ColorMode::Extension => dynamic_color(node, total_size),
+        ColorMode::Age => age_color(node, now),
+        ColorMode::Delta => delta_color(app.size_before(node).unwrap_or(node.size), node.size, max_delta),
+    }
+}
+
+// растущая плитка поверх уже нарисованного содержимого директории, пока идёт анимация входа
+fn render_zoom(f: &mut Frame, app: &mut App, main_area: Rect) {
+    let Some(zoom) = &app.zoom else {
+        return;
+    };
+    let t = zoom.start.elapsed().as_secs_f64() / ZOOM_DURATION.as_secs_f64();
+    if t >= 1.0 {
+        app.zoom = None;
+        return;
+    }
+    // быстро в начале и плавно в конце
+    let t = 1.0 - (1.0 - t) * (1.0 - t);
+    let lerp = |from: u16, to: u16| (from as f64 + (to as f64 - from as f64) * t).round() as u16;
+    let rect = Rect {
+        x: lerp(zoom.from.x, main_area.x),
+        y: lerp(zoom.from.y, main_area.y),
+        width: lerp(zoom.from.width, main_area.width),
+        height: lerp(zoom.from.height, main_area.height),
+    };
+    let Some(rect) = clip_rect(rect, main_area) else {
+        return;
+    };
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(Color::DarkGray))
+        .style(Style::default().bg(zoom.color));
+    let label = Paragraph::new(truncate_middle(&zoom.name, rect.width.saturating_sub(2) as usize))
+        .centered()
+        .style(Style::default().fg(Color::White).add_modifier(Modifier::BOLD))
+        .block(block);
+    f.render_widget(Clear, rect);
+    f.render_widget(label, rect);
+}
 
-    let main_area = chunks[0];
-    let status_area = chunks[1];
+// весь холст режима прокрутки в уменьшенном виде, видимая область заштрихована
+fn render_minimap(f: &mut Frame, app: &App, area: Rect, total_size: u64, now: SystemTime, max_delta: u64) {
+    let inner = area.inner(Margin::new(1, 1));
+    let (scale_x, scale_y) = app.minimap_scale(inner);
+    let view = app.view_area;
+    let lines: Vec<Line> = (0..inner.height)
+        .map(|row| {
+            let y0 = row as f64 * scale_y;
+            let y1 = y0 + scale_y;
+            let canvas_y = view.y + ((row as f64 + 0.5) * scale_y) as u16;
+            let spans: Vec<Span> = (0..inner.width)
+                .map(|col| {
+                    let x0 = col as f64 * scale_x;
+                    let x1 = x0 + scale_x;
+                    let canvas_x = view.x + ((col as f64 + 0.5) * scale_x) as u16;
+                    let bg = app.layout.iter()
+                        .find(|(r, _)| canvas_x >= r.x && canvas_x < r.right() && canvas_y >= r.y && canvas_y < r.bottom())
+                        .map_or(Color::Reset, |(_, n)| tile_color(app, n, total_size, now, max_delta));
+                    let visible = x1 > app.offset_x as f64
+                        && x0 < (app.offset_x + view.width) as f64
+                        && y1 > app.offset_y as f64
+                        && y0 < (app.offset_y + view.height) as f64;
+                    let symbol = if visible { "▒" } else { " " };
+                    Span::styled(symbol, Style::default().bg(bg).fg(Color::White))
+                })
+                .collect();
+            Line::from(spans)
+        })
+        .collect();
+    let minimap = Paragraph::new(lines).block(
+        Block::default()
+            .borders(Borders::ALL)
+            .border_style(Style::default().fg(Color::Yellow)),
+    );
+    f.render_widget(Clear, area);
+    f.render_widget(minimap, area);
+}
+
+fn ui(f: &mut Frame, app: &mut App) {
+    let units = app.units;
+    let (breadcrumb_area, main_area, legend_area, status_area) = screen_areas(f.area(), app.show_legend);
+    app.breadcrumb_area = breadcrumb_area;
+
+    let crumbs = app.breadcrumbs();
+    let mut spans = Vec::new();
+    for (i, (label, _)) in crumbs.iter().enumerate() {
+        if i > 0 {
+            spans.push(Span::styled(BREADCRUMB_SEPARATOR, Style::default().fg(Color::DarkGray)));
+        }
+        let style = if i + 1 == crumbs.len() {
+            Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD)
+        } else {
+            Style::default().fg(Color::Gray)
+        };
+        spans.push(Span::styled(label.clone(), style));
+    }
+    f.render_widget(
+        Paragraph::new(Line::from(spans)).style(Style::default().bg(Color::Rgb(20, 20, 30))),
+        breadcrumb_area,
+    );
 
     let current_node = app.find_node(&app.current_dir).unwrap_or(&app.root);
     let total_size = current_node.size;
+    let current_size_str = app.size_label(&TileInfo::from(current_node));
     let current_name = current_node.name.clone();
+    let (current_files, current_dirs) = (current_node.file_count, current_node.dir_count);
+    let current_mtime_range = mtime_range_hint(current_node);
+    let size_label = if app.disk_usage { tr("Размер на диске", "Disk usage") } else { tr("Размер", "Size") };
+    let search_query = app.search_query.to_lowercase();
+    let mut sort_hint = format!(" | {}: {}", tr("Сортировка", "Sort"), sort_label(app.sort_mode));
+    if app.scan_options.dirs_first {
+        sort_hint += &format!(", {}", tr("папки первыми", "folders first"));
+    }
+    if app.group_by_extension {
+        sort_hint += &format!(" | {}", tr("по расширениям", "by extension"));
+    }
+    if app.collapse_chains {
+        sort_hint += &format!(" | {}", tr("цепочки свёрнуты", "chains collapsed"));
+    }
+    match app.view_filter {
+        ViewFilter::Files => sort_hint += &format!(" | {}", tr("только файлы", "files only")),
+        ViewFilter::Dirs => sort_hint += &format!(" | {}", tr("только папки", "folders only")),
+        ViewFilter::All => {}
+    }
+    if let Some(depth) = app.render_depth {
+        sort_hint += &format!(" | {}: {}", tr("Уровней", "Levels"), depth);
+    }
+    if app.scan_options.respect_gitignore {
+        sort_hint += &format!(
+            " | {}: {}",
+            tr("Пропущено по .gitignore", "Skipped by .gitignore"),
+            app.ignored_entries.values().sum::<u64>()
+        );
+    }
+    let now = SystemTime::now();
+    let max_delta = app.layout.iter()
+        .filter_map(|(_, n)| app.size_before(n).map(|before| before.abs_diff(n.size)))
+        .max()
+        .unwrap_or(0);
+
+    // контекст всего диска показываем только в корне сканирования
+    let disk_hint = match app.filesystem_space {
+        Some((total, free)) if app.current_dir == app.root.path => format!(
+            " | {}: {} {} {}, {} {}",
+            tr("Диск", "Disk"),
+            format_size(total.saturating_sub(free), units),
+            tr("занято из", "used of"),
+            format_size(total, units),
+            format_size(free, units),
+            tr("свободно", "free")
+        ),
+        _ => String::new(),
+    };
 
     // another optimization
-    for (rect, node) in &app.layout {
+    let tiles: &[(Rect, TileInfo)] = if app.list_view { &[] } else { &app.layout };
+    for (rect, node) in tiles {
 
         let screen_x = rect.x as i32 - app.offset_x as i32;
         let screen_y = rect.y as i32 - app.offset_y as i32;
@@ -732,79 +3170,269 @@ fn ui(f: &mut Frame, app: &mut App) {
             draw_rect.y = draw_rect.y.saturating_sub(app.offset_y);
         }
 
-        if let Some(clipped_rect) = clip_rect(draw_rect, main_area) {
-            let is_selected = app.selected.as_ref().map_or(false, |p| p == &node.path);
-            let is_other = node.name == "Прочее";
-            let bg_color = dynamic_color(node, total_size, is_other);
+        if let Some(mut clipped_rect) = clip_rect(draw_rect, main_area) {
+            let is_selected = app.selected.as_ref() == Some(&node.path);
+            let over_budget = app.warn_size.is_some_and(|limit| node.size > limit);
+            let mut bg_color = tile_color(app, node, total_size, now, max_delta);
+            if app.dense {
+                // без рамок соседние плитки разделяет щель в одну клетку справа и снизу
+                if clipped_rect.width > 1 {
+                    clipped_rect.width -= 1;
+                }
+                if clipped_rect.height > 1 {
+                    clipped_rect.height -= 1;
+                }
+                if is_selected {
+                    bg_color = THEME.get().and_then(|t| t.selection).unwrap_or_else(|| brighten(bg_color));
+                }
+            }
 
             let border_style = if is_selected {
-                Style::default().fg(Color::Yellow)
+                Style::default().fg(THEME.get().and_then(|t| t.selection).unwrap_or(Color::Yellow))
+            } else if name_matches(&node.name, &search_query) {
+                Style::default().fg(Color::Magenta).add_modifier(Modifier::BOLD)
+            } else if over_budget {
+                Style::default().fg(Color::Red).add_modifier(Modifier::BOLD)
             } else {
                 Style::default().fg(Color::DarkGray)
             };
 
-            let block = Block::default()
+            // у директорий прямые углы, у файлов скруглённые; без цвета выбор и совпадения видны по толщине рамки
+            let border_type = if app.monochrome && is_selected {
+                ratatui::widgets::BorderType::Double
+            } else if app.monochrome && name_matches(&node.name, &search_query) {
+                ratatui::widgets::BorderType::Thick
+            } else if node.is_dir {
+                ratatui::widgets::BorderType::Plain
+            } else {
+                ratatui::widgets::BorderType::Rounded
+            };
+            let mut block = Block::default()
                 .borders(Borders::ALL)
                 .border_style(border_style)
-                .border_type(ratatui::widgets::BorderType::Rounded);
+                .border_type(border_type);
+            if node.incomplete {
+                block = block.border_set(DASHED_BORDER);
+            }
 
-            let text = if clipped_rect.width > 12 && clipped_rect.height > 4 {
-                let size_str = if node.size < 1024 {
-                    format!("{} байт", node.size)
+            // без рамки подпись помещается и в узкую плитку, лишние строки обрежет сама плитка
+            let (min_width, min_height, padding) = if app.dense { (4, 0, 0) } else { (12, 4, 2) };
+            let text = if clipped_rect.width > min_width && clipped_rect.height > min_height {
+                let size_str = format_size(node.size, units);
+                let percent = if total_size == 0 {
+                    0.0
+                } else {
+                    node.size as f64 / total_size as f64 * 100.0
+                };
+                let icon = if !app.show_icons || node.is_aggregated {
+                    ""
+                } else if node.is_dir {
+                    "📁 "
+                } else {
+                    "📄 "
+                };
+                let icon = if over_budget { format!("! {}", icon) } else { icon.to_string() };
+                let label_width = (clipped_rect.width as usize).saturating_sub(padding + Span::raw(icon.as_str()).width());
+                let name = if app.show_paths {
+                    let relative = node.path.strip_prefix(&app.current_dir).unwrap_or(&node.path);
+                    // вместо служебного имени из пути "Прочее" или группы пишем её название
+                    let relative = if is_synthetic_path(relative) { relative.with_file_name(&node.name) } else { relative.to_path_buf() };
+                    truncate_start(&relative.to_string_lossy(), label_width)
                 } else {
-                    SizeFormatter::new(node.size, DECIMAL).to_string()
+                    truncate_middle(&node.name, label_width)
                 };
+                let label = format!("{}{}", icon, name);
+                let percent = format!("{:.1}%", percent);
+                // на штриховке подпись читается только с пробелами по краям
+                let pad = |text: String| if app.monochrome { format!(" {} ", text) } else { text };
                 vec![
-                    Line::from(node.name.clone()).centered(),
-                    Line::from(size_str).centered(),
+                    Line::from(pad(label)).centered(),
+                    Line::from(pad(size_str)).centered(),
+                    Line::from(pad(percent)).centered(),
                 ]
             } else {
                 vec![]
             };
 
-            let paragraph = Paragraph::new(text)
-                .block(block)
+            let mut paragraph = Paragraph::new(text)
                 .style(Style::default().bg(bg_color).fg(Color::White))
                 .alignment(ratatui::layout::Alignment::Center);
+            if app.dense {
+                // рамки нет: совпадение с поиском и превышение бюджета видны по подписи
+                if over_budget {
+                    paragraph = paragraph.style(Style::default().bg(bg_color).fg(Color::Red).add_modifier(Modifier::BOLD));
+                }
+                if name_matches(&node.name, &search_query) {
+                    paragraph = paragraph.style(Style::default().bg(bg_color).fg(Color::Magenta).add_modifier(Modifier::BOLD));
+                }
+                if app.monochrome && is_selected {
+                    paragraph = paragraph.style(Style::default().add_modifier(Modifier::REVERSED));
+                }
+            } else {
+                paragraph = paragraph.block(block);
+            }
 
+            if app.monochrome {
+                let symbol = shade(bg_color);
+                let buf = f.buffer_mut();
+                for y in clipped_rect.top()..clipped_rect.bottom() {
+                    for x in clipped_rect.left()..clipped_rect.right() {
+                        buf[(x, y)].set_symbol(symbol);
+                    }
+                }
+            }
             f.render_widget(paragraph, clipped_rect);
         }
     }
 
+    // без объяснения пустая директория выглядит как одна серая плитка
+    if total_size == 0 {
+        let message = Paragraph::new(vec![
+            Line::from(""),
+            Line::from(tr(
+                "Директория пуста или содержит только файлы нулевого размера",
+                "Directory is empty or contains only zero-byte files",
+            ))
+            .centered(),
+        ])
+        .style(Style::default().fg(Color::Gray))
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .border_style(Style::default().fg(Color::DarkGray))
+                .border_type(ratatui::widgets::BorderType::Rounded),
+        );
+        f.render_widget(Clear, main_area);
+        f.render_widget(message, main_area);
+    }
+
+    if app.list_view && total_size > 0 {
+        render_ranked_list(f, app, main_area);
+    }
+
+    render_zoom(f, app, main_area);
+
+    // на маленьком холсте миникарта закрыла бы всё полезное
+    app.minimap_area = if app.scroll_mode && !app.list_view && main_area.width >= 24 && main_area.height >= 12 {
+        let width = (main_area.width / 3).min(20);
+        let height = (main_area.height / 3).min(10);
+        Rect::new(main_area.right() - width, main_area.y, width, height)
+    } else {
+        Rect::default()
+    };
+    if !app.minimap_area.is_empty() {
+        render_minimap(f, app, app.minimap_area, total_size, now, max_delta);
+    }
+
+    let mut other_contents = None;
+    // рамка и ": " после подписи
+    let path_width = (status_area.width as usize).saturating_sub(4 + Span::raw(tr("Путь", "Path")).width());
     let mut status_lines = if let Some(selected_path) = &app.selected {
-        if let Some(node) = app.get_node_at(app.mouse_pos.0, app.mouse_pos.1) {
-            let name = selected_path.file_name().map_or("".to_string(), |s| s.to_string_lossy().into_owned());
-            let size_str = if node.size < 1024 {
-                format!("{} байт", node.size)
+        if let Some(node) = app.selected_info() {
+            let name = if node.is_aggregated {
+                node.name.clone()
             } else {
-                SizeFormatter::new(node.size, DECIMAL).to_string()
+                selected_path.file_name().map_or("".to_string(), |s| s.to_string_lossy().into_owned())
             };
+            let size_str = app.size_label(&node);
+            let mut counts = if node.is_bucket {
+                format!(" | {}: {}", tr("Файлов", "Files"), format_count(node.file_count))
+            } else if node.is_dir {
+                format!(
+                    " | {}: {}, {}: {}",
+                    tr("Файлов", "Files"),
+                    format_count(node.file_count),
+                    tr("папок", "dirs"),
+                    format_count(node.dir_count)
+                )
+            } else {
+                String::new()
+            };
+            if let Some(dir) = app.find_node(selected_path).filter(|_| !node.is_bucket) {
+                counts += &mtime_range_hint(dir);
+            }
+            if node.is_aggregated && !node.is_bucket {
+                let other = app.find_node(&node.path).filter(|other| other.is_aggregated);
+                if let Some(other) = other {
+                    counts = format!(" | {}: {}", tr("Файлов", "Files"), format_count(other.file_count));
+                    if other.threshold > 0 {
+                        counts += &format!(" | {}: {}", tr("Порог", "Threshold"), format_size(other.threshold, units));
+                    }
+                    let names: Vec<String> = other.children.iter()
+                        .take(20)
+                        .map(|c| format!("{} {}", c.name, format_size(c.size, units)))
+                        .collect();
+                    let mut line = names.join(", ");
+                    if other.children.len() > names.len() {
+                        line += &format!(", … +{}", other.children.len() - names.len());
+                    }
+                    if !line.is_empty() {
+                        other_contents = Some(line);
+                    }
+                }
+            }
             vec![
-                Line::from(format!("Путь: {}", selected_path.display())),
-                Line::from(format!("Имя: {} | Размер: {}", name, size_str)),
+                Line::from(format!("{}: {}", tr("Путь", "Path"), truncate_middle(&app.shown_path(selected_path).to_string_lossy(), path_width))),
+                Line::from(format!("{}: {} | {}: {}{}{}", tr("Имя", "Name"), name, size_label, size_str, counts, sort_hint)),
             ]
         } else {
             vec![
-                Line::from(format!("Путь: {}", selected_path.display())),
-                Line::from("Нет данных о файле".to_string()),
+                Line::from(format!("{}: {}", tr("Путь", "Path"), truncate_middle(&app.shown_path(selected_path).to_string_lossy(), path_width))),
+                Line::from(format!("{}{}", tr("Нет данных о файле", "No data for this file"), sort_hint)),
             ]
         }
     } else {
-        let size_str = if total_size < 1024 {
-            format!("{} байт", total_size)
-        } else {
-            SizeFormatter::new(total_size, DECIMAL).to_string()
-        };
+        let size_str = current_size_str;
         vec![
-            Line::from(format!("Текущая директория: {}", app.current_dir.display())),
-            Line::from(format!("Имя: {} | Размер: {}", current_name, size_str)),
+            Line::from(format!(
+                "{}: {}{}",
+                tr("Текущая директория", "Current directory"),
+                app.shown_path(&app.current_dir).display(),
+                disk_hint
+            )),
+            Line::from(format!(
+                "{}: {} | {}: {} | {}: {}, {}: {}{}{}",
+                tr("Имя", "Name"),
+                current_name,
+                size_label,
+                size_str,
+                tr("Файлов", "Files"),
+                format_count(current_files),
+                tr("папок", "dirs"),
+                format_count(current_dirs),
+                current_mtime_range,
+                sort_hint
+            )),
         ]
     };
 
-    if app.scroll_mode {
+    if let Some(message) = &app.status_message {
+        status_lines.push(Line::from(message.clone()).style(Style::default().fg(Color::Cyan)));
+    } else if app.search_mode {
+        status_lines.push(
+            Line::from(format!("{}: {}_", tr("Поиск", "Search"), app.search_query)).style(Style::default().fg(Color::Magenta)),
+        );
+    } else if !app.search_query.is_empty() {
+        status_lines.push(
+            Line::from(format!(
+                "{}: {} | {}",
+                tr("Поиск", "Search"),
+                app.search_query,
+                tr("n: следующее совпадение | Esc: сбросить", "n: next match | Esc: clear")
+            ))
+                .style(Style::default().fg(Color::Magenta)),
+        );
+    } else if let Some(contents) = other_contents {
+        status_lines.push(Line::from(contents).style(Style::default().fg(Color::Gray)));
+    } else if app.scroll_mode {
         let scroll_hint = format!(
-            "←/→/↑/↓: прокрутка | H/L: быстрая прокрутка | Смещение: {}, {}",
-            app.offset_x, app.offset_y
+            "{}: {}, {}",
+            tr(
+                "←/→/↑/↓: прокрутка | H/L: быстрая прокрутка | 0: в начало | Смещение",
+                "←/→/↑/↓: pan | H/L: fast pan | 0: reset | Offset"
+            ),
+            app.offset_x,
+            app.offset_y
         );
         status_lines.push(Line::from(scroll_hint).style(Style::default().fg(Color::Yellow)));
     }
@@ -818,4 +3446,436 @@ fn ui(f: &mut Frame, app: &mut App) {
         );
 
     f.render_widget(status, status_area);
-}
\ No newline at end of file
+
+    // рост или уменьшение директории за пересканирования, справа в первой строке статуса
+    if let Some(history) = app.size_history.get(&app.current_dir).filter(|h| h.len() > 1) {
+        let min = history.iter().copied().min().unwrap_or(0);
+        let max = history.iter().copied().max().unwrap_or(0);
+        // от минимума, иначе колебания в пару мегабайт на фоне гигабайт не видны; минимум — нижняя ступенька
+        let range = (max - min).max(1) as f64;
+        let data: Vec<u64> = history.iter().map(|&size| 1 + ((size - min) as f64 / range * 7.0).round() as u64).collect();
+        let inner = status_area.inner(Margin::new(1, 1));
+        let width = (data.len() as u16).min(inner.width);
+        let sparkline = Sparkline::default()
+            .data(&data)
+            .max(8)
+            .style(Style::default().fg(Color::Cyan).bg(Color::Rgb(20, 20, 30)));
+        f.render_widget(sparkline, Rect::new(inner.right() - width, inner.y, width, 1));
+    }
+
+    if app.show_legend {
+        render_legend(f, app, legend_area);
+    }
+
+    if app.show_help {
+        render_help(f);
+    }
+
+    if app.show_errors {
+        render_scan_errors(f, app);
+    }
+
+    if app.show_histogram
+        && let Some(node) = app.find_node(&app.current_dir)
+    {
+        render_histogram(f, node, units);
+    }
+
+    if let Some(details) = &app.details {
+        render_details(f, details, app.details_scroll);
+    }
+
+    if let Some(path) = &app.pending_delete {
+        let popup_area = centered_rect(60, 5, f.area());
+        let popup = Paragraph::new(vec![
+            Line::from(format!("{} {}?", tr("Удалить", "Delete"), path.display())).centered(),
+            Line::from(tr("y - удалить, любая другая клавиша - отмена", "y - delete, any other key - cancel")).centered(),
+        ])
+        .style(Style::default().bg(Color::Rgb(60, 20, 20)).fg(Color::White))
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .border_style(Style::default().fg(Color::Red))
+                .title(tr("Удаление", "Delete")),
+        );
+        f.render_widget(Clear, popup_area);
+        f.render_widget(popup, popup_area);
+    }
+}
+
+// 'v': дети текущей директории строками с полосой доли, как в классических анализаторах диска
+fn render_ranked_list(f: &mut Frame, app: &App, area: Rect) {
+    let units = app.units;
+    let current = app.find_node(&app.current_dir).unwrap_or(&app.root);
+    let entries = app.list_entries();
+    let selected = app.selected.as_ref().and_then(|p| entries.iter().position(|n| &n.path == p));
+    let inner_height = area.height.saturating_sub(2) as usize;
+    // выбранная строка всегда на экране
+    let start = selected.map_or(0, |i| (i + 1).saturating_sub(inner_height));
+    let bar_width = (area.width as usize / 4).clamp(5, 30);
+
+    let lines: Vec<Line> = entries.iter()
+        .enumerate()
+        .skip(start)
+        .take(inner_height)
+        .map(|(i, node)| {
+            let fraction = if current.size == 0 { 0.0 } else { node.size as f64 / current.size as f64 };
+            let filled = (fraction * bar_width as f64).round() as usize;
+            let number = if i < 9 { format!("{:>2} ", i + 1) } else { "   ".to_string() };
+            let icon = if !app.show_icons || node.is_aggregated {
+                ""
+            } else if node.is_dir {
+                "📁 "
+            } else {
+                "📄 "
+            };
+            let mut line = Line::from(vec![
+                Span::styled(number, Style::default().fg(Color::Yellow)),
+                Span::styled("█".repeat(filled), Style::default().fg(Color::Cyan)),
+                Span::styled("░".repeat(bar_width - filled.min(bar_width)), Style::default().fg(Color::DarkGray)),
+                Span::raw(format!(" {:>5.1}% {:>10}  {}{}", fraction * 100.0, format_size(node.size, units), icon, node.name)),
+            ]);
+            if selected == Some(i) {
+                line = line.style(if app.monochrome {
+                    Style::default().add_modifier(Modifier::REVERSED)
+                } else {
+                    Style::default().bg(Color::Rgb(60, 60, 90))
+                });
+            }
+            line
+        })
+        .collect();
+
+    let list = Paragraph::new(lines)
+        .style(Style::default().bg(Color::Rgb(20, 20, 30)).fg(Color::White))
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .border_style(Style::default().fg(Color::DarkGray))
+                .title(tr("Список (v) — 1-9: войти", "List (v) — 1-9: enter")),
+        );
+    f.render_widget(Clear, area);
+    f.render_widget(list, area);
+}
+
+fn render_scan_progress(f: &mut Frame, ctx: &ScanContext) {
+    let path = CURRENT_PATH.lock().unwrap().clone();
+    let popup_area = centered_rect(70, 4, f.area());
+    let popup = Paragraph::new(vec![
+        Line::from(path),
+        Line::from(format!(
+            "📄 {}: {} | ↧ {} {}",
+            tr("файлов", "files"),
+            ctx.scanned_files.load(Ordering::Relaxed),
+            tr("глубина", "depth"),
+            CURRENT_DEPTH.load(Ordering::Relaxed)
+        )),
+    ])
+    .style(Style::default().bg(Color::Rgb(20, 20, 30)).fg(Color::White))
+    .block(
+        Block::default()
+            .borders(Borders::ALL)
+            .border_style(Style::default().fg(Color::Cyan))
+            .title(tr("Сканирую... (Esc — отмена)", "Scanning... (Esc to cancel)")),
+    );
+    f.render_widget(Clear, popup_area);
+    f.render_widget(popup, popup_area);
+}
+
+fn render_scan_errors(f: &mut Frame, app: &App) {
+    let area = f.area();
+    let popup_area = centered_rect(80, area.height.saturating_sub(4).min(app.scan_errors.len() as u16 + 2).max(3), area);
+    let lines: Vec<Line> = if app.scan_errors.is_empty() {
+        vec![Line::from(tr("Все пути прочитаны без ошибок", "All paths were read without errors")).centered()]
+    } else {
+        app.scan_errors.iter()
+            .skip(app.errors_scroll)
+            .map(|(path, error)| {
+                Line::from(vec![
+                    Span::styled(path.display().to_string(), Style::default().fg(Color::Yellow)),
+                    Span::raw(format!(": {}", error)),
+                ])
+            })
+            .collect()
+    };
+    let popup = Paragraph::new(lines)
+        .style(Style::default().bg(Color::Rgb(20, 20, 30)).fg(Color::White))
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .border_style(Style::default().fg(Color::Red))
+                .title(format!(
+                    "{} ({}) — {}",
+                    tr("Ошибки чтения", "Read errors"),
+                    app.scan_errors.len(),
+                    tr("↑/↓ прокрутка, Esc - закрыть", "↑/↓ scroll, Esc - close")
+                )),
+        );
+    f.render_widget(Clear, popup_area);
+    f.render_widget(popup, popup_area);
+}
+
+const HISTOGRAM_BINS: [&str; 4] = ["< 1K", "1K – 1M", "1M – 1G", "≥ 1G"];
+
+// (число файлов, их общий размер) по корзинам HISTOGRAM_BINS; K — 1000 или 1024, как в --units.
+// "Прочее" без списка файлов (из импорта) — сумма многих файлов, в корзины не попадает
+fn size_histogram(node: &Node, units: Units) -> [(u64, u64); 4] {
+    fn collect(node: &Node, base: u64, bins: &mut [(u64, u64); 4]) {
+        if node.is_dir {
+            for child in &node.children {
+                collect(child, base, bins);
+            }
+            return;
+        }
+        if node.is_aggregated {
+            return;
+        }
+        let bin = match node.size {
+            size if size < base => 0,
+            size if size < base.pow(2) => 1,
+            size if size < base.pow(3) => 2,
+            _ => 3,
+        };
+        bins[bin].0 += 1;
+        bins[bin].1 += node.size;
+    }
+
+    let base = match units {
+        Units::Binary => 1024,
+        Units::Decimal => 1000,
+    };
+    let mut bins = [(0, 0); 4];
+    collect(node, base, &mut bins);
+    bins
+}
+
+// два столбчатых графика: сколько файлов в каждой корзине и сколько места они занимают
+fn render_histogram(f: &mut Frame, node: &Node, units: Units) {
+    use ratatui::widgets::{Bar, BarChart, BarGroup};
+
+    let bins = size_histogram(node, units);
+    let popup_area = centered_rect(64, 24, f.area());
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(Color::Cyan))
+        .title(format!("{}: {} (g / Esc — {})", tr("Размеры файлов", "File sizes"), node.name, tr("закрыть", "close")));
+    let inner = block.inner(popup_area);
+    f.render_widget(Clear, popup_area);
+    f.render_widget(block.style(Style::default().bg(Color::Rgb(20, 20, 30)).fg(Color::White)), popup_area);
+
+    let [count_area, size_area] = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Percentage(50), Constraint::Percentage(50)])
+        .areas(inner);
+    let bar_width = (inner.width.saturating_sub(5) / 4).max(1);
+    let chart = |title: &'static str, bars: Vec<Bar<'static>>, color: Color| {
+        BarChart::default()
+            .block(Block::default().title(title))
+            .data(BarGroup::default().bars(&bars))
+            .bar_width(bar_width)
+            .bar_gap(1)
+            .bar_style(Style::default().fg(color))
+            .value_style(Style::default().fg(Color::Black).bg(color))
+    };
+    let counts = HISTOGRAM_BINS.iter().zip(&bins)
+        .map(|(label, &(count, _))| Bar::default().value(count).text_value(format_count(count)).label(Line::from(*label)))
+        .collect();
+    let sizes = HISTOGRAM_BINS.iter().zip(&bins)
+        .map(|(label, &(_, size))| Bar::default().value(size).text_value(format_size(size, units)).label(Line::from(*label)))
+        .collect();
+    f.render_widget(chart(tr("Файлов", "Files"), counts, Color::Cyan), count_area);
+    f.render_widget(chart(tr("Объём", "Total size"), sizes, Color::Green), size_area);
+}
+
+fn render_details(f: &mut Frame, details: &[(String, String)], scroll: u16) {
+    let area = f.area();
+    let key_width = details.iter().map(|(k, _)| Span::raw(k.as_str()).width()).max().unwrap_or(0);
+    let lines: Vec<Line> = details.iter()
+        .map(|(key, value)| {
+            let pad = " ".repeat(key_width - Span::raw(key.as_str()).width());
+            Line::from(vec![
+                Span::styled(format!(" {}{}", key, pad), Style::default().fg(Color::Yellow)),
+                Span::raw(format!("  {}", value)),
+            ])
+        })
+        .collect();
+    let scroll = scroll.min(lines.len().saturating_sub(1) as u16);
+
+    let popup_area = centered_rect(90, lines.len() as u16 + 2, area);
+    let popup = Paragraph::new(lines)
+        .style(Style::default().bg(Color::Rgb(20, 20, 30)).fg(Color::White))
+        .scroll((scroll, 0))
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .border_style(Style::default().fg(Color::Cyan))
+                .title(tr("Подробности (↑/↓ прокрутка, i / Esc - закрыть)", "Details (↑/↓ scroll, i / Esc - close)")),
+        );
+    f.render_widget(Clear, popup_area);
+    f.render_widget(popup, popup_area);
+}
+
+fn render_help(f: &mut Frame) {
+    let area = f.area();
+    f.buffer_mut().set_style(area, Style::default().fg(Color::DarkGray).add_modifier(Modifier::DIM));
+
+    let keybindings = keybindings();
+    let key_width = keybindings.iter().map(|(k, _)| Span::raw(*k).width()).max().unwrap_or(0);
+    let lines: Vec<Line> = keybindings.iter()
+        .map(|(key, action)| {
+            let pad = " ".repeat(key_width - Span::raw(*key).width());
+            Line::from(vec![
+                Span::styled(format!(" {}{}", key, pad), Style::default().fg(Color::Yellow)),
+                Span::raw(format!("  {}", action)),
+            ])
+        })
+        .collect();
+
+    let popup_area = centered_rect(70, lines.len() as u16 + 2, area);
+    let popup = Paragraph::new(lines)
+        .style(Style::default().bg(Color::Rgb(20, 20, 30)).fg(Color::White))
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .border_style(Style::default().fg(Color::Yellow))
+                .title(tr("Справка (? / Esc / q - закрыть)", "Help (? / Esc / q - close)")),
+        );
+    f.render_widget(Clear, popup_area);
+    f.render_widget(popup, popup_area);
+}
+
+fn centered_rect(width: u16, height: u16, area: Rect) -> Rect {
+    let width = width.min(area.width);
+    let height = height.min(area.height);
+    Rect {
+        x: area.x + (area.width - width) / 2,
+        y: area.y + (area.height - height) / 2,
+        width,
+        height,
+    }
+}
+
+#[cfg(test)]
+#[path = "test_support.rs"]
+mod test_support;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{dir, file};
+
+    #[test]
+    fn real_dir_named_other_is_not_styled_as_bucket() {
+        let real = dir("Прочее", vec![file("x", 100)]);
+        let bucket = Node {
+            name: "Прочее".to_string(),
+            size: 100,
+            path: PathBuf::from("/t").join(OTHER_MARKER),
+            is_dir: true,
+            is_aggregated: true,
+            file_count: 5,
+            ..Default::default()
+        };
+        let is_gray = |color| matches!(color, Color::Rgb(r, g, b) if r == g && g == b);
+        assert!(is_gray(dynamic_color(&TileInfo::from(&bucket), 200)));
+        assert!(!is_gray(dynamic_color(&TileInfo::from(&real), 200)));
+        assert!(!TileInfo::from(&real).is_aggregated);
+    }
+
+    #[test]
+    fn find_node_resolves_other_bucket() {
+        let opts = ScanOptions::default();
+        let root_path = PathBuf::from("/t");
+        let mut files = vec![file("big", 1_000_000)];
+        files.extend((0..30).map(|i| file(&format!("s{}", i), 10)));
+        let sub = assemble_dir(&root_path.join("sub"), vec![file("sub/x", 500_000), file("sub/y", 5)], false, &opts, 1);
+        files.push(sub);
+        let app = App::new(assemble_dir(&root_path, files, false, &opts, 0));
+
+        let other = app.find_node(&root_path.join(OTHER_MARKER)).expect("Прочее в корне");
+        assert!(other.is_aggregated);
+        assert_eq!(other.file_count, 30);
+        assert_eq!(app.find_node(&root_path).map(|n| n.path.clone()), Some(root_path.clone()));
+        // файлы внутри "Прочее" находятся по своим настоящим путям
+        assert_eq!(app.find_node(&root_path.join("s7")).map(|n| n.size), Some(10));
+
+        let nested = app.find_node(&root_path.join("sub").join(OTHER_MARKER)).expect("Прочее в sub");
+        assert!(nested.is_aggregated);
+        assert!(app.find_node(&root_path.join("sub")).is_some_and(|n| !n.is_aggregated));
+    }
+
+    #[test]
+    fn subtree_rescan_replaces_only_its_ignored_entries() {
+        let root = dir("root", vec![dir("root/a", vec![file("root/a/x", 10)]), dir("root/b", vec![file("root/b/y", 20)])]);
+        let mut app = App::new(root.clone());
+        let ctx = ScanContext::default();
+        ctx.ignored_entries.lock().unwrap().extend([(root.path.clone(), 1), (root.path.join("a"), 2), (root.path.join("b"), 4)]);
+        app.take_scan_results(&root.path, &ctx);
+
+        let ctx = ScanContext::default();
+        ctx.ignored_entries.lock().unwrap().insert(root.path.join("a"), 3);
+        app.take_scan_results(&root.path.join("a"), &ctx);
+        assert_eq!(app.ignored_entries.values().sum::<u64>(), 8);
+    }
+
+    #[test]
+    fn update_entry_fixes_up_ancestors() {
+        let opts = ScanOptions::default();
+        let root_path = PathBuf::from("/t");
+        let sub_path = root_path.join("sub");
+        let mut small: Vec<Node> = (0..30).map(|i| file(&format!("sub/s{}", i), 10)).collect();
+        small.push(file("sub/big", 1_000_000));
+        let sub = assemble_dir(&sub_path, small, false, &opts, 1);
+        let mut app = App::new(assemble_dir(&root_path, vec![sub, file("top", 500)], false, &opts, 0));
+        assert_eq!(app.root.size, 1_000_800);
+
+        // файл внутри "Прочее" вырос и перестал быть мелким
+        app.update_entry(&sub_path.join("s3"), Some(file("sub/s3", 400_000)));
+        assert_eq!(app.root.size, 1_400_790);
+        assert!(app.find_node(&sub_path.join(OTHER_MARKER)).is_some_and(|o| o.file_count == 29));
+        assert!(app.find_node(&sub_path).is_some_and(|n| n.children.iter().any(|c| c.name == "sub/s3")));
+
+        app.update_entry(&sub_path.join("new"), Some(file("sub/new", 5)));
+        app.update_entry(&sub_path.join("big"), None);
+        assert_eq!(app.root.size, 400_795);
+        assert_eq!(app.root.file_count, 32);
+        assert!(app.find_node(&sub_path.join("big")).is_none());
+    }
+
+    #[test]
+    fn size_histogram_bin_edges() {
+        let root = dir("root", vec![file("a", 999), file("b", 1000), file("c", 1023), file("d", 1024)]);
+        assert_eq!(size_histogram(&root, Units::Decimal).map(|(count, _)| count), [1, 3, 0, 0]);
+        assert_eq!(size_histogram(&root, Units::Binary).map(|(count, _)| count), [3, 1, 0, 0]);
+    }
+
+    #[test]
+    fn size_histogram_skips_imported_other() {
+        let other = Node { is_aggregated: true, file_count: 500, ..file("Other", 5_000_000_000) };
+        let root = dir("root", vec![file("a", 10), other]);
+        assert_eq!(size_histogram(&root, Units::Decimal), [(1, 10), (0, 0), (0, 0), (0, 0)]);
+    }
+
+    #[test]
+    fn extension_bucket_is_not_a_path_on_disk() {
+        // настоящий файл может называться так же, как группа
+        let root = dir("root", vec![file("root/a.jpg", 10), file("root/*.jpg", 5)]);
+        let grouped = group_by_extension(&root, SortMode::Size, false);
+        let bucket = grouped.children.iter().find(|c| c.name == "*.jpg").expect("группа .jpg");
+        assert!(is_synthetic_path(&bucket.path));
+        assert_eq!(real_dir(&bucket.path), root.path);
+        let app = App::new(root.clone());
+        assert!(app.find_node(&bucket.path).is_none());
+        assert!(app.find_node(&root.path.join("*.jpg")).is_some_and(|n| n.size == 5));
+    }
+
+    #[test]
+    fn filter_remainder_is_not_a_path_on_disk() {
+        let root = dir("root", vec![file("root/(files)", 10), dir("root/sub", vec![file("root/sub/x", 20)])]);
+        let filtered = filter_view(&root, ViewFilter::Dirs);
+        let hidden = filtered.children.iter().find(|c| c.is_aggregated).expect("плитка скрытых файлов");
+        assert!(is_synthetic_path(&hidden.path));
+        assert_eq!(real_dir(&hidden.path), root.path);
+        assert!(App::new(root.clone()).find_node(&hidden.path).is_none());
+    }
+}
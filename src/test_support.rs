@@ -0,0 +1,24 @@
+// Узлы-заготовки для тестов библиотеки и бинарника; main.rs подключает этот же файл через #[path]
+use crate::Node;
+use std::path::PathBuf;
+
+pub fn file(name: &str, size: u64) -> Node {
+    Node {
+        name: name.to_string(),
+        size,
+        path: PathBuf::from("/t").join(name),
+        file_count: 1,
+        ..Default::default()
+    }
+}
+
+pub fn dir(name: &str, children: Vec<Node>) -> Node {
+    Node {
+        name: name.to_string(),
+        size: children.iter().map(|c| c.size).sum(),
+        path: PathBuf::from("/t").join(name),
+        is_dir: true,
+        children,
+        ..Default::default()
+    }
+}
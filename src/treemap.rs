@@ -0,0 +1,314 @@
+// Раскладка treemap: делит прямоугольник между детьми узла пропорционально размерам
+use crate::Node;
+use ratatui::layout::Rect;
+
+// depth: сколько ещё уровней раскрывать (--render-depth), None — без ограничения
+// longest: направление выбирается по форме area, horizontal тогда не важен
+pub fn layout_tree<'a>(node: &'a Node, area: Rect, horizontal: bool, longest: bool, min_tile: u16, depth: Option<usize>) -> Vec<(Rect, &'a Node)> {
+    if node.children.is_empty() || depth == Some(0) || area.width < min_tile || area.height < min_tile {
+        return vec![(area, node)];
+    }
+    // ячейка примерно вдвое выше своей ширины, поэтому высота считается за две
+    let horizontal = if longest { area.width as u32 >= area.height as u32 * 2 } else { horizontal };
+
+    let children: Vec<&'a Node> = node.children.iter()
+        .filter(|c| c.size > 0)
+        .collect();
+
+    if children.is_empty() {
+        return vec![(area, node)];
+    }
+
+    // Делим по сумме детей, а не node.size: после удаления или при жёстких ссылках они могут расходиться
+    let total: f64 = children.iter().map(|c| c.size as f64).sum();
+    let primary = if horizontal { area.width } else { area.height };
+    let primary_dim = primary as f64;
+    let sizes: Vec<f64> = children.iter()
+        .map(|c| (c.size as f64 / total) * primary_dim)
+        .collect();
+
+    let mut integer_sizes: Vec<u16> = sizes.iter().map(|&v| (v.floor() as u16).max(min_tile)).collect();
+    let allocated: u32 = integer_sizes.iter().map(|&v| v as u32).sum();
+
+    if allocated <= primary as u32 {
+        let remainder = primary.saturating_sub(allocated as u16);
+        let mut fractional: Vec<(usize, f64)> = sizes.iter()
+            .enumerate()
+            .filter(|&(i, &v)| v >= integer_sizes[i] as f64)
+            .map(|(i, &v)| (i, v.fract()))
+            .collect();
+        fractional.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
+
+        for &(i, _) in fractional.iter().take(remainder as usize) {
+            integer_sizes[i] += 1;
+        }
+    } else {
+        // Минимальный размер раздул сумму: забираем лишнее у самых крупных плиток
+        let mut excess = allocated - primary as u32;
+        let mut order: Vec<usize> = (0..integer_sizes.len()).collect();
+        order.sort_by(|&a, &b| integer_sizes[b].cmp(&integer_sizes[a]));
+        while excess > 0 {
+            let mut shrunk = false;
+            for &i in &order {
+                if excess == 0 {
+                    break;
+                }
+                if integer_sizes[i] > min_tile {
+                    integer_sizes[i] -= 1;
+                    excess -= 1;
+                    shrunk = true;
+                }
+            }
+            if !shrunk {
+                // Всем не хватит места — хвостовые плитки отбрасываются ниже
+                break;
+            }
+        }
+    }
+
+    let mut result = Vec::new();
+    let mut current_pos = if horizontal { area.x } else { area.y };
+    let secondary_start = if horizontal { area.y } else { area.x };
+    let secondary_size = if horizontal { area.height } else { area.width };
+
+    // первая плитка последнего уложенного ребёнка: остаток места достанется ему целиком
+    let mut last_start = 0;
+    for (i, &child) in children.iter().enumerate() {
+        let mut size_primary = integer_sizes[i];
+
+        let available = if horizontal {
+            area.right().saturating_sub(current_pos)
+        } else {
+            area.bottom().saturating_sub(current_pos)
+        };
+        if size_primary > available {
+            size_primary = available;
+        }
+        if size_primary < min_tile || size_primary == 0 {
+            break;
+        }
+
+        let child_rect = if horizontal {
+            Rect {
+                x: current_pos,
+                y: secondary_start,
+                width: size_primary,
+                height: secondary_size,
+            }
+        } else {
+            Rect {
+                x: secondary_start,
+                y: current_pos,
+                width: secondary_size,
+                height: size_primary,
+            }
+        };
+
+        last_start = result.len();
+        result.extend(layout_tree(child, child_rect, !horizontal, longest, min_tile, depth.map(|d| d - 1)));
+        current_pos += size_primary;
+    }
+
+    let remaining = if horizontal {
+        area.right().saturating_sub(current_pos)
+    } else {
+        area.bottom().saturating_sub(current_pos)
+    };
+    // последний ребёнок мог разбиться поперёк, тогда растягиваем все его плитки у дальнего края, а не только последнюю
+    if remaining > 0 {
+        for (rect, _) in &mut result[last_start..] {
+            if horizontal && rect.right() == current_pos {
+                rect.width += remaining;
+            } else if !horizontal && rect.bottom() == current_pos {
+                rect.height += remaining;
+            }
+        }
+    }
+
+    result
+}
+
+pub fn layout_squarified<'a>(node: &'a Node, area: Rect, min_tile: u16, depth: Option<usize>) -> Vec<(Rect, &'a Node)> {
+    if node.children.is_empty() || depth == Some(0) || area.width < min_tile || area.height < min_tile {
+        return vec![(area, node)];
+    }
+
+    let children: Vec<&'a Node> = node.children.iter()
+        .filter(|c| c.size > 0)
+        .collect();
+    let total: f64 = children.iter().map(|c| c.size as f64).sum();
+
+    if children.is_empty() || total <= 0.0 {
+        return vec![(area, node)];
+    }
+
+    // ячейка терминала примерно вдвое выше своей ширины, поэтому считаем в "квадратных" единицах
+    let width = area.width as f64;
+    let height = area.height as f64 * 2.0;
+    let sizes: Vec<f64> = children.iter()
+        .map(|c| c.size as f64 / total * width * height)
+        .collect();
+
+    let mut result = Vec::new();
+    for (i, (fx, fy, fw, fh)) in squarify(&sizes, width, height).into_iter().enumerate() {
+        let x1 = area.x + fx.round() as u16;
+        let x2 = (area.x + (fx + fw).round() as u16).min(area.right());
+        let y1 = area.y + (fy / 2.0).round() as u16;
+        let y2 = (area.y + ((fy + fh) / 2.0).round() as u16).min(area.bottom());
+        if x2 <= x1 || y2 <= y1 {
+            continue;
+        }
+
+        let child_rect = Rect {
+            x: x1,
+            y: y1,
+            width: x2 - x1,
+            height: y2 - y1,
+        };
+        result.extend(layout_squarified(children[i], child_rect, min_tile, depth.map(|d| d - 1)));
+    }
+
+    result
+}
+
+fn squarify(sizes: &[f64], width: f64, height: f64) -> Vec<(f64, f64, f64, f64)> {
+    fn worst(row: &[f64], side: f64) -> f64 {
+        let sum: f64 = row.iter().sum();
+        let max = row.iter().cloned().fold(f64::MIN, f64::max);
+        let min = row.iter().cloned().fold(f64::MAX, f64::min);
+        let side2 = side * side;
+        let sum2 = sum * sum;
+        (side2 * max / sum2).max(sum2 / (side2 * min))
+    }
+
+    let mut rects = Vec::with_capacity(sizes.len());
+    let (mut x, mut y, mut w, mut h) = (0.0, 0.0, width, height);
+    let mut start = 0;
+
+    while start < sizes.len() {
+        let side = w.min(h);
+        let mut end = start + 1;
+        let mut best = worst(&sizes[start..end], side);
+        while end < sizes.len() {
+            let next = worst(&sizes[start..end + 1], side);
+            if next > best {
+                break;
+            }
+            best = next;
+            end += 1;
+        }
+
+        let row_sum: f64 = sizes[start..end].iter().sum();
+        if w >= h {
+            // строка укладывается столбцом вдоль левого края
+            let col_w = if h > 0.0 { row_sum / h } else { 0.0 };
+            let mut cy = y;
+            for &size in &sizes[start..end] {
+                let ch = if col_w > 0.0 { size / col_w } else { 0.0 };
+                rects.push((x, cy, col_w, ch));
+                cy += ch;
+            }
+            x += col_w;
+            w -= col_w;
+        } else {
+            let row_h = if w > 0.0 { row_sum / w } else { 0.0 };
+            let mut cx = x;
+            for &size in &sizes[start..end] {
+                let cw = if row_h > 0.0 { size / row_h } else { 0.0 };
+                rects.push((cx, y, cw, row_h));
+                cx += cw;
+            }
+            y += row_h;
+            h -= row_h;
+        }
+        start = end;
+    }
+
+    rects
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{dir, file};
+
+    // плитки внутри area, не перекрываются и вместе закрывают её целиком
+    fn assert_tiles_cover(tiles: &[(Rect, &Node)], area: Rect) {
+        for (rect, _) in tiles {
+            assert!(
+                rect.x >= area.x && rect.y >= area.y && rect.right() <= area.right() && rect.bottom() <= area.bottom(),
+                "{:?} вне {:?}", rect, area
+            );
+        }
+        for (i, (a, _)) in tiles.iter().enumerate() {
+            for (b, _) in &tiles[i + 1..] {
+                assert!(!a.intersects(*b), "{:?} перекрывает {:?}", a, b);
+            }
+        }
+        let covered: u32 = tiles.iter().map(|(r, _)| r.width as u32 * r.height as u32).sum();
+        assert_eq!(covered, area.width as u32 * area.height as u32);
+    }
+
+    #[test]
+    fn layout_tree_covers_area() {
+        let root = dir("root", vec![
+            dir("a", vec![file("a1", 500), file("a2", 300), file("a3", 7)]),
+            file("b", 900),
+            dir("c", vec![file("c1", 40), dir("d", vec![file("d1", 20), file("d2", 25)])]),
+            file("e", 3),
+        ]);
+        for area in [Rect::new(0, 0, 100, 30), Rect::new(5, 2, 37, 11), Rect::new(0, 0, 200, 7)] {
+            for (horizontal, longest) in [(true, false), (false, false), (true, true)] {
+                let tiles = layout_tree(&root, area, horizontal, longest, 3, None);
+                assert_tiles_cover(&tiles, area);
+            }
+        }
+    }
+
+    #[test]
+    fn layout_tree_single_child() {
+        let root = dir("root", vec![file("only", 42)]);
+        let area = Rect::new(0, 0, 40, 12);
+        let tiles = layout_tree(&root, area, true, false, 3, None);
+        assert_eq!(tiles.len(), 1);
+        assert_eq!(tiles[0].0, area);
+        assert_eq!(tiles[0].1.name, "only");
+    }
+
+    #[test]
+    fn layout_tree_zero_size() {
+        let area = Rect::new(0, 0, 40, 12);
+        let empty = dir("root", vec![file("a", 0), file("b", 0)]);
+        let tiles = layout_tree(&empty, area, true, false, 3, None);
+        assert_eq!(tiles.len(), 1);
+        assert_eq!(tiles[0].1.name, "root");
+
+        // пустой файл рядом с непустыми места не занимает
+        let mixed = dir("root", vec![file("a", 10), file("zero", 0), file("b", 30)]);
+        let tiles = layout_tree(&mixed, area, true, false, 3, None);
+        assert!(tiles.iter().all(|(_, n)| n.name != "zero"));
+        assert_tiles_cover(&tiles, area);
+    }
+
+    #[test]
+    fn layout_tree_narrow_area_does_not_overflow() {
+        // 40 плиток по min_tile 3 не помещаются в 30 колонок
+        let root = dir("root", (0..40).map(|i| file(&format!("f{}", i), 1 + i % 3)).collect());
+        let area = Rect::new(2, 1, 30, 10);
+        assert!(area.width < 40 * 3);
+        for longest in [false, true] {
+            let tiles = layout_tree(&root, area, true, longest, 3, None);
+            assert_tiles_cover(&tiles, area);
+        }
+    }
+
+    #[test]
+    fn layout_tree_area_smaller_than_min_tile() {
+        let root = dir("root", vec![file("a", 10), file("b", 20)]);
+        let area = Rect::new(3, 4, 2, 2);
+        let tiles = layout_tree(&root, area, true, false, 3, None);
+        assert_eq!(tiles.len(), 1);
+        assert_eq!(tiles[0].0, area);
+        assert_eq!(tiles[0].1.name, "root");
+    }
+}